@@ -0,0 +1,58 @@
+use std::{collections::VecDeque, time::Duration};
+
+/// The number of recent landing latencies the buffer is chosen from.
+const MAX_SAMPLES: usize = 20;
+
+/// The share of recent submissions expected to land by the deadline, as a percentile.
+const PERCENTILE: usize = 75;
+
+/// Learns the buffer time from how long recent submissions took to confirm.
+///
+/// A hash submitted `buffer` seconds before it is due lands on time if confirmation takes
+/// at most that long, and the program accepts it up to `TOLERANCE` seconds early or late.
+/// Tracking a high percentile of latency keeps most submissions on time without giving up
+/// more hashing time than needed.
+pub struct AdaptiveBuffer {
+    min: u64,
+    max: u64,
+    latencies: VecDeque<Duration>,
+    buffer_time: u64,
+}
+
+impl AdaptiveBuffer {
+    pub fn new(initial: u64, min: u64, max: u64) -> Self {
+        Self {
+            min,
+            max,
+            latencies: VecDeque::new(),
+            buffer_time: initial.clamp(min, max),
+        }
+    }
+
+    /// Records the time from sending a transaction to its confirmation.
+    pub fn record_landing(&mut self, latency: Duration) {
+        self.record(latency);
+    }
+
+    /// Records a submission that failed or was not confirmed, as if it took the longest
+    /// buffer to land, so that misses make the buffer more conservative.
+    pub fn record_miss(&mut self) {
+        self.record(Duration::from_secs(self.max));
+    }
+
+    fn record(&mut self, latency: Duration) {
+        self.latencies.push_back(latency);
+        if self.latencies.len().gt(&MAX_SAMPLES) {
+            self.latencies.pop_front();
+        }
+        let mut sorted: Vec<Duration> = self.latencies.iter().copied().collect();
+        sorted.sort();
+        let index = (sorted.len() * PERCENTILE / 100).min(sorted.len() - 1);
+        let secs = sorted[index].as_secs_f64().ceil() as u64;
+        self.buffer_time = secs.clamp(self.min, self.max);
+    }
+
+    pub fn buffer_time(&self) -> u64 {
+        self.buffer_time
+    }
+}
@@ -0,0 +1,142 @@
+use std::io;
+#[cfg(target_os = "linux")]
+use std::{collections::HashSet, fs, mem};
+
+/// Where the kernel describes each logical CPU's topology.
+#[cfg(target_os = "linux")]
+const CPU_DIR: &str = "/sys/devices/system/cpu";
+
+/// Where hashing threads run and how they yield to the rest of the process.
+#[derive(Clone, Debug, Default)]
+pub struct Placement {
+    /// The logical CPUs to pin threads to, in turn, if pinning.
+    #[cfg(target_os = "linux")]
+    cores: Option<Vec<usize>>,
+    nice: Option<i32>,
+}
+
+impl Placement {
+    /// Pins to an explicit core list like `0,2,4-7`, or with `pin` to every allowed core,
+    /// one per physical core before any SMT siblings.
+    #[cfg(target_os = "linux")]
+    pub fn new(pin: bool, cores: Option<&str>, nice: Option<i32>) -> Result<Self, String> {
+        let cores = match cores {
+            Some(list) => Some(parse_core_list(list)?),
+            None if pin => Some(core_order()),
+            None => None,
+        };
+        Ok(Self { cores, nice })
+    }
+
+    /// Pinning threads to cores is only supported on Linux.
+    #[cfg(not(target_os = "linux"))]
+    pub fn new(pin: bool, cores: Option<&str>, nice: Option<i32>) -> Result<Self, String> {
+        if pin || cores.is_some() {
+            return Err("Pinning threads to cores is only supported on Linux".to_string());
+        }
+        Ok(Self { nice })
+    }
+
+    /// Applies the placement to the calling thread, the `worker`th hashing thread.
+    #[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+    pub fn apply(&self, worker: usize) -> io::Result<()> {
+        #[cfg(target_os = "linux")]
+        if let Some(cores) = self.cores.as_ref().filter(|cores| !cores.is_empty()) {
+            pin(cores[worker % cores.len()])?;
+        }
+        if let Some(nice) = self.nice {
+            // On Linux, each thread has its own nice value. Elsewhere, this sets the
+            // whole process's.
+            if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) }.ne(&0) {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn pin(cpu: usize) -> io::Result<()> {
+    unsafe {
+        let mut set: libc::cpu_set_t = mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        if libc::sched_setaffinity(0, mem::size_of::<libc::cpu_set_t>(), &set).ne(&0) {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// The logical CPUs this process may run on.
+#[cfg(target_os = "linux")]
+fn allowed_cpus() -> Option<HashSet<usize>> {
+    unsafe {
+        let mut set: libc::cpu_set_t = mem::zeroed();
+        if libc::sched_getaffinity(0, mem::size_of::<libc::cpu_set_t>(), &mut set).ne(&0) {
+            return None;
+        }
+        Some(
+            (0..libc::CPU_SETSIZE as usize)
+                .filter(|cpu| libc::CPU_ISSET(*cpu, &set))
+                .collect(),
+        )
+    }
+}
+
+/// The allowed logical CPUs, taking the first of each physical core before the SMT
+/// siblings, so that a thread count up to the physical core count never shares a core.
+#[cfg(target_os = "linux")]
+fn core_order() -> Vec<usize> {
+    let allowed = allowed_cpus();
+    let mut cpus: Vec<(usize, String)> = fs::read_dir(CPU_DIR)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let cpu = entry
+                .file_name()
+                .to_str()?
+                .strip_prefix("cpu")?
+                .parse::<usize>()
+                .ok()?;
+            let topology = entry.path().join("topology");
+            let read = |name: &str| fs::read_to_string(topology.join(name)).ok();
+            let core = format!(
+                "{}:{}",
+                read("physical_package_id")?.trim(),
+                read("core_id")?.trim()
+            );
+            Some((cpu, core))
+        })
+        .filter(|(cpu, _)| allowed.as_ref().map_or(true, |set| set.contains(cpu)))
+        .collect();
+    if cpus.is_empty() {
+        return (0..num_cpus::get()).collect();
+    }
+    cpus.sort();
+    let mut seen = HashSet::new();
+    let (first, siblings): (Vec<_>, Vec<_>) = cpus
+        .into_iter()
+        .partition(|(_, core)| seen.insert(core.clone()));
+    first
+        .into_iter()
+        .chain(siblings)
+        .map(|(cpu, _)| cpu)
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn parse_core_list(list: &str) -> Result<Vec<usize>, String> {
+    let invalid = || format!("Invalid core list `{}`, expected e.g. 0,2,4-7", list);
+    let mut cores = vec![];
+    for part in list.split(',') {
+        let (start, end) = part.trim().split_once('-').unwrap_or((part, part));
+        let start = start.trim().parse::<usize>().map_err(|_| invalid())?;
+        let end = end.trim().parse::<usize>().map_err(|_| invalid())?;
+        if start.gt(&end) || end.ge(&(libc::CPU_SETSIZE as usize)) {
+            return Err(invalid());
+        }
+        cores.extend(start..=end);
+    }
+    Ok(cores)
+}
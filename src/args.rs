@@ -1,5 +1,12 @@
+use std::{net::SocketAddr, path::PathBuf};
+
 use clap::{arg, Parser};
 
+use crate::{
+    affinity::Placement, benchmark::Threads, bus_strategy::BusStrategy, mine::DeadlinePolicy,
+    reset_policy::ResetPolicy, send_and_confirm::Sender,
+};
+
 #[derive(Parser, Debug)]
 pub struct BalanceArgs {
     #[arg(
@@ -17,9 +24,29 @@ pub struct BenchmarkArgs {
         short,
         value_name = "THREAD_COUNT",
         help = "The number of threads to use during the benchmark",
-        default_value = "1"
+        default_value = "1",
+        value_parser = clap::value_parser!(u64).range(1..)
     )]
     pub threads: u64,
+
+    #[arg(
+        long,
+        value_name = "INDEX",
+        help = "The index of this machine when several machines mine the same authority",
+        default_value = "0"
+    )]
+    pub machine_index: u64,
+
+    #[arg(
+        long,
+        value_name = "COUNT",
+        help = "The number of machines splitting the nonce space",
+        default_value = "1"
+    )]
+    pub machine_count: u64,
+
+    #[command(flatten)]
+    pub placement: PlacementArgs,
 }
 
 #[derive(Parser, Debug)]
@@ -63,10 +90,83 @@ pub struct MineArgs {
         long,
         short,
         value_name = "THREAD_COUNT",
-        help = "The number of CPU threads to allocate to mining",
+        help = "The number of CPU threads to allocate to mining, or auto to calibrate at startup",
         default_value = "1"
     )]
-    pub threads: u64,
+    pub threads: Threads,
+
+    #[arg(
+        long,
+        value_name = "INDEX",
+        help = "The index of this machine when several machines mine the same authority",
+        default_value = "0"
+    )]
+    pub machine_index: u64,
+
+    #[arg(
+        long,
+        value_name = "COUNT",
+        help = "The number of machines splitting the nonce space",
+        default_value = "1"
+    )]
+    pub machine_count: u64,
+
+    #[command(flatten)]
+    pub placement: PlacementArgs,
+
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        help = "The average share of each thread's time to spend hashing, from 1 to 100",
+        default_value = "100",
+        value_parser = clap::value_parser!(u64).range(1..=100)
+    )]
+    pub cpu_limit: u64,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Read the CPU limit from this file while mining, so it can be changed at runtime"
+    )]
+    pub cpu_limit_file: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "CELSIUS",
+        help = "Shed hashing threads while the CPU is hotter than this"
+    )]
+    pub thermal_high: Option<f64>,
+
+    #[arg(
+        long,
+        value_name = "CELSIUS",
+        help = "Restore hashing threads once the CPU is cooler than this. Defaults to 10 below --thermal-high"
+    )]
+    pub thermal_low: Option<f64>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "The directory holding thermal_zone*/temp sensor files",
+        default_value = "/sys/class/thermal"
+    )]
+    pub thermal_path: PathBuf,
+
+    #[arg(
+        long,
+        value_name = "WINDOWS",
+        help = "Only mine within these daily windows, e.g. 22:00-06:00,12:00-14:00"
+    )]
+    pub schedule: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "ZONE",
+        help = "The timezone the schedule is in: a UTC offset like +02:00, or a zone like Europe/Berlin that follows daylight saving time",
+        default_value = "+00:00",
+        allow_hyphen_values = true
+    )]
+    pub timezone: String,
 
     #[arg(
         long,
@@ -76,11 +176,266 @@ pub struct MineArgs {
         default_value = "5"
     )]
     pub buffer_time: u64,
+
+    #[arg(
+        long,
+        help = "Adjust the buffer time each round from how long recent submissions took to land"
+    )]
+    pub adaptive_buffer: bool,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "The smallest buffer time chosen by --adaptive-buffer",
+        default_value = "1"
+    )]
+    pub min_buffer_time: u64,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "The largest buffer time chosen by --adaptive-buffer",
+        default_value = "20"
+    )]
+    pub max_buffer_time: u64,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Mine with every private key in this file, one per line, sharing one hashing pool"
+    )]
+    pub keys_file: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "STRATEGY",
+        help = "How to pick a bus: random, richest, weighted, or a fixed bus index",
+        default_value = "random"
+    )]
+    pub bus_strategy: BusStrategy,
+
+    #[arg(
+        long,
+        value_name = "POLICY",
+        help = "When to reset the epoch: always-when-due, never, only-if-busses-empty, or probabilistic[:P]",
+        default_value = "always-when-due"
+    )]
+    pub reset_policy: ResetPolicy,
+
+    #[arg(
+        long,
+        value_name = "DIFFICULTY",
+        help = "Stop mining and submit as soon as a hash of this difficulty is found. Targets below the min difficulty count as the min difficulty"
+    )]
+    pub target_difficulty: Option<u32>,
+
+    #[arg(
+        long,
+        value_name = "DIFFICULTY",
+        help = "Stop mining once a hash of this difficulty is found, but submit at the usual deadline"
+    )]
+    pub max_difficulty: Option<u32>,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Stop mining this many seconds past the submission time, even below the min difficulty"
+    )]
+    pub hard_deadline: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "POLICY",
+        help = "What to do when the hard deadline stops threads still below the min difficulty: abandon the round and keep mining the challenge, or submit the best hash if another thread reached the min difficulty",
+        default_value = "abandon"
+    )]
+    pub hard_deadline_policy: DeadlinePolicy,
+
+    #[arg(
+        long,
+        value_name = "SOL",
+        help = "The price of one ORE in SOL. When set, unprofitable hashes are not submitted"
+    )]
+    pub ore_price: Option<f64>,
+
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        help = "The minimum expected profit over transaction cost required to submit",
+        default_value = "0",
+        allow_negative_numbers = true
+    )]
+    pub profit_margin: f64,
+
+    #[arg(
+        long,
+        help = "Keep an unprofitable hash and mine the same challenge for a better one, once per challenge and only while the retry can submit before the liveness penalty"
+    )]
+    pub defer_unprofitable: bool,
+
+    #[arg(
+        long,
+        value_name = "AMOUNT",
+        help = "Claim the stake balance to --auto-claim-to whenever it exceeds this much ORE",
+        requires = "auto_claim_to"
+    )]
+    pub auto_claim_threshold: Option<f64>,
+
+    #[arg(
+        long,
+        value_name = "WALLET_ADDRESS",
+        help = "The wallet to send auto-claimed ORE to",
+        requires = "auto_claim_threshold"
+    )]
+    pub auto_claim_to: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "AMOUNT",
+        help = "Stake any ORE in the wallet beyond this much, checked every --auto-stake-interval. Cannot be combined with auto-claim, which would move the same ORE back out",
+        conflicts_with = "auto_claim_threshold"
+    )]
+    pub auto_stake_reserve: Option<f64>,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "How often to check the wallet for ORE to auto-stake",
+        default_value = "300"
+    )]
+    pub auto_stake_interval: u64,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Append a JSON line describing each mining round to this file"
+    )]
+    pub journal: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        help = "Serve Prometheus metrics over HTTP on this address, e.g. 0.0.0.0:9100"
+    )]
+    pub metrics_addr: Option<SocketAddr>,
+
+    #[arg(
+        long,
+        help = "Simulate each mine transaction instead of sending it. No SOL is spent"
+    )]
+    pub dry_run: bool,
+
+    #[arg(
+        long,
+        value_name = "SENDER",
+        help = "How to submit mine transactions",
+        default_value = "jito"
+    )]
+    pub sender: Sender,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "Hash for a pool server at this WebSocket address instead of submitting solo",
+        conflicts_with = "keys_file"
+    )]
+    pub pool: Option<String>,
+
+    #[arg(
+        long,
+        help = "Mine solo if the pool server cannot be reached",
+        requires = "pool"
+    )]
+    pub pool_fallback: bool,
+
+    /// Seeds random bus and reset choices, for reproducible simulations.
+    #[arg(skip)]
+    pub seed: Option<u64>,
+}
+
+/// Where hashing threads run, shared by the benchmark and mine commands.
+#[derive(Parser, Debug)]
+pub struct PlacementArgs {
+    #[arg(
+        long,
+        help = "Pin each hashing thread to its own core, using every physical core before SMT siblings. Linux only"
+    )]
+    pub pin_threads: bool,
+
+    #[arg(
+        long,
+        value_name = "LIST",
+        help = "Pin hashing threads to these cores in turn, e.g. 0,2,4-7. Linux only"
+    )]
+    pub cores: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "NICENESS",
+        help = "The nice value of hashing threads, from -20 to 19. Higher values yield to submission work",
+        allow_negative_numbers = true,
+        value_parser = clap::value_parser!(i32).range(-20..=19)
+    )]
+    pub nice: Option<i32>,
+}
+
+impl PlacementArgs {
+    pub fn placement(&self) -> Placement {
+        Placement::new(self.pin_threads, self.cores.as_deref(), self.nice).unwrap_or_else(|err| {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        })
+    }
 }
 
 #[derive(Parser, Debug)]
 pub struct RewardsArgs {}
 
+#[derive(Parser, Debug)]
+pub struct SimulateArgs {
+    #[command(flatten)]
+    pub mine: MineArgs,
+
+    #[arg(
+        long,
+        value_name = "SEED",
+        help = "Seed for challenges, landing outcomes, bus and reset choices, and the simulated keypair",
+        default_value = "0"
+    )]
+    pub seed: u64,
+
+    #[arg(
+        long,
+        value_name = "PROBABILITY",
+        help = "The probability that a sent transaction lands, between 0 and 1",
+        default_value = "1.0"
+    )]
+    pub landing_probability: f64,
+
+    #[arg(
+        long,
+        value_name = "MILLISECONDS",
+        help = "The time between sending a transaction and it landing",
+        default_value = "1000"
+    )]
+    pub latency_ms: u64,
+
+    #[arg(
+        long,
+        value_name = "DIFFICULTY",
+        help = "The minimum difficulty accepted by the simulated program",
+        default_value = "8"
+    )]
+    pub min_difficulty: u32,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Stop after this many seconds and print the session summary"
+    )]
+    pub duration: Option<u64>,
+}
+
 #[derive(Parser, Debug)]
 pub struct StakeArgs {
     #[arg(
@@ -1,57 +1,75 @@
-use std::{sync::Arc, time::Instant};
+use std::{
+    str::FromStr,
+    time::{Duration, Instant},
+};
 
 use solana_rpc_client::spinner;
 
-use crate::{args::BenchmarkArgs, Miner};
+use crate::{
+    affinity::Placement,
+    args::BenchmarkArgs,
+    hash_pool::{HashJob, HashPool, NoncePartition},
+    Miner,
+};
 
 const TEST_DURATION: i64 = 30;
 
+/// How long each thread count is measured for during calibration, in seconds.
+const CALIBRATION_DURATION: u64 = 5;
+
+/// The hashrate gain, in percent, that justifies running another thread.
+const MIN_GAIN: f64 = 3.0;
+
+/// A number of hashing threads, or `auto` to pick one by calibration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Threads {
+    Auto,
+    Count(u64),
+}
+
+impl FromStr for Threads {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            _ => match s.parse::<u64>() {
+                Ok(n) if n.gt(&0) => Ok(Self::Count(n)),
+                _ => Err("expected auto or a thread count of at least 1".to_string()),
+            },
+        }
+    }
+}
+
 impl Miner {
     pub async fn benchmark(&self, args: BenchmarkArgs) {
         // Check num threads
         self.check_num_cores(args.threads);
+        let partition =
+            NoncePartition::new(args.machine_index, args.machine_count).unwrap_or_else(|err| {
+                eprintln!("error: {}", err);
+                std::process::exit(1);
+            });
 
         // Dispatch job to each thread
         let challenge = [0; 32];
-        let progress_bar = Arc::new(spinner::new_progress_bar());
+        let progress_bar = spinner::new_progress_bar();
         progress_bar.set_message(format!(
             "Benchmarking. This will take {} sec...",
             TEST_DURATION
         ));
-        let handles: Vec<_> = (0..args.threads)
-            .map(|i| {
-                std::thread::spawn({
-                    move || {
-                        let timer = Instant::now();
-                        let first_nonce = u64::MAX.saturating_div(args.threads).saturating_mul(i);
-                        let mut nonce = first_nonce;
-                        loop {
-                            // Create hash
-                            let _hx = drillx::hash(&challenge, &nonce.to_le_bytes());
-
-                            // Increment nonce
-                            nonce += 1;
-
-                            // Exit if time has elapsed
-                            if (timer.elapsed().as_secs() as i64).ge(&TEST_DURATION) {
-                                break;
-                            }
-                        }
-
-                        // Return hash count
-                        nonce - first_nonce
-                    }
-                })
-            })
-            .collect();
+        let pool = HashPool::new(args.threads, partition, args.placement.placement());
+        pool.dispatch(HashJob {
+            challenge,
+            nonces: 0..u64::MAX,
+            cutoff: Instant::now() + Duration::from_secs(TEST_DURATION as u64),
+            hard_cutoff: None,
+            min_difficulty: 0,
+            target_difficulty: None,
+        });
 
-        // Join handles and return best nonce
-        let mut total_nonces = 0;
-        for h in handles {
-            if let Ok(count) = h.join() {
-                total_nonces += count;
-            }
-        }
+        // Join results and sum hash counts
+        let total_nonces = pool.collect(|| {}).total_hashes();
 
         // Update log
         progress_bar.finish_with_message(format!(
@@ -59,4 +77,64 @@ impl Miner {
             total_nonces.saturating_div(TEST_DURATION as u64),
         ));
     }
+
+    /// Measures the hashrate over increasing thread counts and returns the smallest count
+    /// within `MIN_GAIN` of the best. Counts past the physical cores only hash on SMT
+    /// siblings, so they must beat the best physical count by that margin to be chosen.
+    pub fn calibrate_threads(partition: NoncePartition, placement: &Placement) -> u64 {
+        let physical = num_cpus::get_physical() as u64;
+        let logical = num_cpus::get() as u64;
+        let mut counts: Vec<u64> = std::iter::successors(Some(1u64), |n| Some(n * 2))
+            .take_while(|n| n.lt(&logical))
+            .chain([physical, logical])
+            .collect();
+        counts.sort();
+        counts.dedup();
+
+        // Measure each thread count in turn
+        println!("Calibrating threads...");
+        let mut rates = vec![];
+        for threads in counts {
+            let progress_bar = spinner::new_progress_bar();
+            progress_bar.set_message(format!("{} threads...", threads));
+            let pool = HashPool::new(threads, partition, placement.clone());
+            pool.dispatch(HashJob {
+                challenge: rand::random(),
+                nonces: 0..u64::MAX,
+                cutoff: Instant::now() + Duration::from_secs(CALIBRATION_DURATION),
+                hard_cutoff: None,
+                min_difficulty: 0,
+                target_difficulty: None,
+            });
+            let hashrate = pool.collect(|| {}).total_hashes() as f64 / CALIBRATION_DURATION as f64;
+            progress_bar.finish_with_message(format!(
+                "{} threads: {:.1} H/sec{}",
+                threads,
+                hashrate,
+                if threads.gt(&physical) { " (SMT)" } else { "" }
+            ));
+            rates.push((threads, hashrate));
+        }
+
+        // Only count SMT siblings if they beat the physical cores by the margin
+        let margin = 1.0 + MIN_GAIN / 100.0;
+        let max_rate = |smt: bool| {
+            rates
+                .iter()
+                .filter(|(threads, _)| threads.gt(&physical) == smt)
+                .fold(0.0f64, |max, (_, rate)| max.max(*rate))
+        };
+        let mut best = max_rate(false);
+        if max_rate(true).gt(&(best * margin)) {
+            best = max_rate(true);
+        }
+
+        // Take the fewest threads within the margin of the best
+        let threads = rates
+            .iter()
+            .find(|(_, rate)| rate.ge(&(best / margin)))
+            .map_or(1, |(threads, _)| *threads);
+        println!("Using {} threads", threads);
+        threads
+    }
 }
@@ -0,0 +1,167 @@
+use std::str::FromStr;
+
+use ore_api::{
+    consts::{BUS_ADDRESSES, BUS_COUNT},
+    state::Bus,
+};
+use rand::Rng;
+use solana_program::pubkey::Pubkey;
+
+/// How the miner picks a bus to submit its hash to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BusStrategy {
+    /// Any bus that can cover the payout, uniformly at random.
+    Random,
+    /// The bus with the most rewards left this epoch.
+    Richest,
+    /// A random bus, weighted by its remaining rewards.
+    Weighted,
+    /// Always the bus with the given id, unless it cannot cover the payout.
+    Fixed(usize),
+}
+
+impl FromStr for BusStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(Self::Random),
+            "richest" => Ok(Self::Richest),
+            "weighted" => Ok(Self::Weighted),
+            _ => match s.parse::<usize>() {
+                Ok(i) if i < BUS_COUNT => Ok(Self::Fixed(i)),
+                _ => Err(format!(
+                    "expected random, richest, weighted, or a bus index from 0 to {}",
+                    BUS_COUNT - 1
+                )),
+            },
+        }
+    }
+}
+
+impl BusStrategy {
+    /// Picks a bus for a payout of `reward`, skipping busses in `exclude`. Busses that
+    /// cannot cover the full reward are only used if no other bus can.
+    pub fn select(
+        &self,
+        busses: &[Bus],
+        reward: u64,
+        exclude: &[Pubkey],
+        rng: &mut impl Rng,
+    ) -> Option<Pubkey> {
+        let candidates: Vec<&Bus> = busses
+            .iter()
+            .filter(|bus| !exclude.contains(&bus_address(bus)))
+            .collect();
+        let eligible: Vec<&Bus> = candidates
+            .iter()
+            .copied()
+            .filter(|bus| bus.rewards.ge(&reward))
+            .collect();
+        if eligible.is_empty() {
+            // Fall back to whichever bus can pay out the most
+            return candidates
+                .iter()
+                .max_by_key(|bus| bus.rewards)
+                .map(|bus| bus_address(bus));
+        }
+
+        let bus = match self {
+            Self::Random => eligible[rng.gen_range(0..eligible.len())],
+            Self::Richest => *eligible.iter().max_by_key(|bus| bus.rewards).unwrap(),
+            Self::Weighted => {
+                let total: u64 = eligible.iter().map(|bus| bus.rewards).sum();
+                let mut pick = rng.gen_range(0..total.max(1));
+                let mut selected = eligible[eligible.len() - 1];
+                for bus in eligible.iter() {
+                    if pick < bus.rewards {
+                        selected = bus;
+                        break;
+                    }
+                    pick -= bus.rewards;
+                }
+                selected
+            }
+            Self::Fixed(i) => match eligible.iter().find(|bus| bus.id == *i as u64) {
+                Some(bus) => bus,
+                None => *eligible.iter().max_by_key(|bus| bus.rewards).unwrap(),
+            },
+        };
+        Some(bus_address(bus))
+    }
+}
+
+fn bus_address(bus: &Bus) -> Pubkey {
+    BUS_ADDRESSES[bus.id as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    fn bus(id: u64, rewards: u64) -> Bus {
+        Bus {
+            id,
+            rewards,
+            theoretical_rewards: 0,
+            top_balance: 0,
+        }
+    }
+
+    #[test]
+    fn parses() {
+        assert_eq!("random".parse(), Ok(BusStrategy::Random));
+        assert_eq!("richest".parse(), Ok(BusStrategy::Richest));
+        assert_eq!("weighted".parse(), Ok(BusStrategy::Weighted));
+        assert_eq!("0".parse(), Ok(BusStrategy::Fixed(0)));
+        assert_eq!(
+            (BUS_COUNT - 1).to_string().parse(),
+            Ok(BusStrategy::Fixed(BUS_COUNT - 1))
+        );
+        for invalid in ["", "Random", "-1", "1.5", &BUS_COUNT.to_string()] {
+            assert!(invalid.parse::<BusStrategy>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn selects_eligible_bus() {
+        let busses = [bus(0, 5), bus(1, 50), bus(2, 20)];
+        let mut rng = StdRng::seed_from_u64(0);
+        let select = |strategy: BusStrategy, reward, exclude: &[Pubkey], rng: &mut StdRng| {
+            strategy.select(&busses, reward, exclude, rng)
+        };
+        assert_eq!(
+            select(BusStrategy::Richest, 10, &[], &mut rng),
+            Some(BUS_ADDRESSES[1])
+        );
+        assert_eq!(
+            select(BusStrategy::Fixed(2), 10, &[], &mut rng),
+            Some(BUS_ADDRESSES[2])
+        );
+
+        // A fixed bus that cannot cover the reward gives way to the richest
+        assert_eq!(
+            select(BusStrategy::Fixed(0), 10, &[], &mut rng),
+            Some(BUS_ADDRESSES[1])
+        );
+
+        // Random picks never land on a bus that cannot cover the reward
+        for _ in 0..32 {
+            let picked = select(BusStrategy::Random, 10, &[], &mut rng);
+            assert_ne!(picked, Some(BUS_ADDRESSES[0]));
+            let picked = select(BusStrategy::Weighted, 10, &[], &mut rng);
+            assert_ne!(picked, Some(BUS_ADDRESSES[0]));
+        }
+
+        // Excluded busses are skipped, falling back to the most rewards left
+        let exclude = [BUS_ADDRESSES[1], BUS_ADDRESSES[2]];
+        assert_eq!(
+            select(BusStrategy::Richest, 10, &exclude, &mut rng),
+            Some(BUS_ADDRESSES[0])
+        );
+        let exclude = [BUS_ADDRESSES[0], BUS_ADDRESSES[1], BUS_ADDRESSES[2]];
+        assert_eq!(select(BusStrategy::Random, 10, &exclude, &mut rng), None);
+    }
+}
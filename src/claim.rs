@@ -2,8 +2,9 @@ use std::str::FromStr;
 
 use colored::*;
 use ore_api::consts::MINT_ADDRESS;
-use solana_program::pubkey::Pubkey;
-use solana_sdk::signature::Signer;
+use solana_client::client_error::Result as ClientResult;
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+use solana_sdk::signature::{Signature, Signer};
 use spl_token::amount_to_ui_amount;
 
 use crate::{
@@ -22,28 +23,8 @@ impl Miner {
         let mut ixs = vec![];
         let beneficiary = match args.to {
             Some(to) => {
-                // Create beneficiary token account, if needed
                 let wallet = Pubkey::from_str(&to).expect("Failed to parse wallet address");
-                let benefiary_tokens = spl_associated_token_account::get_associated_token_address(
-                    &wallet,
-                    &MINT_ADDRESS,
-                );
-                if self
-                    .rpc_client
-                    .get_token_account(&benefiary_tokens)
-                    .await
-                    .is_err()
-                {
-                    ixs.push(
-                        spl_associated_token_account::instruction::create_associated_token_account(
-                            &signer.pubkey(),
-                            &wallet,
-                            &ore_api::consts::MINT_ADDRESS,
-                            &spl_token::id(),
-                        ),
-                    );
-                }
-                benefiary_tokens
+                self.beneficiary_tokens(wallet, &mut ixs).await
             }
             None => self.initialize_ata().await,
         };
@@ -77,6 +58,42 @@ impl Miner {
             .ok();
     }
 
+    /// Claims to a wallet without asking for confirmation, creating its token account if
+    /// needed.
+    pub async fn claim_to(&self, wallet: Pubkey, amount: u64) -> ClientResult<Signature> {
+        let mut ixs = vec![];
+        let beneficiary = self.beneficiary_tokens(wallet, &mut ixs).await;
+        ixs.push(ore_api::instruction::claim(
+            self.signer().pubkey(),
+            beneficiary,
+            amount,
+        ));
+        self.send_and_confirm(&ixs, ComputeBudget::Fixed(CU_LIMIT_CLAIM), false)
+            .await
+    }
+
+    /// Returns a wallet's ORE token account, adding an instruction to create it if needed.
+    async fn beneficiary_tokens(&self, wallet: Pubkey, ixs: &mut Vec<Instruction>) -> Pubkey {
+        let benefiary_tokens =
+            spl_associated_token_account::get_associated_token_address(&wallet, &MINT_ADDRESS);
+        if self
+            .rpc_client
+            .get_token_account(&benefiary_tokens)
+            .await
+            .is_err()
+        {
+            ixs.push(
+                spl_associated_token_account::instruction::create_associated_token_account(
+                    &self.signer().pubkey(),
+                    &wallet,
+                    &ore_api::consts::MINT_ADDRESS,
+                    &spl_token::id(),
+                ),
+            );
+        }
+        benefiary_tokens
+    }
+
     async fn initialize_ata(&self) -> Pubkey {
         // Initialize client.
         let signer = self.signer();
@@ -0,0 +1,570 @@
+use std::{
+    collections::{hash_map::Entry, HashMap, VecDeque},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use base64::{prelude::BASE64_STANDARD, Engine};
+use drillx::Solution;
+use ore_api::{
+    consts::{
+        BUS_ADDRESSES, BUS_EPOCH_REWARDS, CONFIG_ADDRESS, EPOCH_DURATION, NOOP_PROGRAM_ID,
+        ONE_MINUTE, ONE_ORE, TOLERANCE,
+    },
+    error::OreError,
+    instruction::{MineArgs, OreInstruction},
+    state::{Bus, Config, Proof},
+};
+use ore_utils::Discriminator;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde_json::{json, Value};
+use solana_client::{
+    client_error::{ClientErrorKind, Result as ClientResult},
+    rpc_request::RpcRequest,
+    rpc_response::{
+        Response, RpcBlockhash, RpcResponseContext, RpcSimulateTransactionResult, RpcVersionInfo,
+    },
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+use solana_program::{
+    hash::hashv, instruction::InstructionError, pubkey::Pubkey,
+    system_instruction::SystemInstruction, system_program, sysvar,
+};
+use solana_sdk::{
+    clock::Clock,
+    compute_budget,
+    signature::Signature,
+    transaction::{Transaction, TransactionError},
+};
+use solana_transaction_status::{TransactionConfirmationStatus, TransactionStatus};
+
+use crate::{
+    profitability::SIGNATURE_FEE,
+    utils::{calculate_reward, proof_pubkey},
+};
+
+/// The unix timestamp the simulated clock starts at.
+const GENESIS_TIMESTAMP: i64 = 1_720_000_000;
+
+/// The simulated slot duration, in milliseconds.
+const SLOT_MS: u128 = 400;
+
+/// The SOL balance every signer starts with, in lamports.
+const INITIAL_SOL_BALANCE: u64 = 1_000_000_000;
+
+/// The reward paid for a hash of the minimum difficulty.
+const BASE_REWARD_RATE: u64 = ONE_ORE / 1000;
+
+/// Parameters of the simulated network.
+pub struct FakeChainConfig {
+    pub seed: u64,
+    pub min_difficulty: u32,
+    pub landing_probability: f64,
+    pub latency: Duration,
+}
+
+/// An in-process stand-in for a Solana RPC node running the ORE program. It implements
+/// just enough of the RPC API for the mining loop: accounts, balances, blockhashes, and
+/// sending, confirming, and simulating transactions.
+pub struct FakeChain {
+    state: Mutex<State>,
+}
+
+struct State {
+    seed: u64,
+    /// Decides which sent transactions land. Other randomness is derived from the seed and
+    /// the ledger, so it does not depend on how often the miner calls the RPC.
+    rng: StdRng,
+    started: Instant,
+    landing_probability: f64,
+    latency: Duration,
+    config: Config,
+    busses: Vec<Bus>,
+    proofs: HashMap<Pubkey, Proof>,
+    balances: HashMap<Pubkey, u64>,
+    pending: VecDeque<(Instant, Transaction)>,
+    statuses: HashMap<Signature, Option<TransactionStatus>>,
+}
+
+/// The result of executing a transaction.
+struct Execution {
+    result: Result<(), TransactionError>,
+    logs: Vec<String>,
+}
+
+impl FakeChain {
+    pub fn new(config: FakeChainConfig) -> Self {
+        let busses = BUS_ADDRESSES
+            .iter()
+            .enumerate()
+            .map(|(id, _)| Bus {
+                id: id as u64,
+                rewards: BUS_EPOCH_REWARDS,
+                theoretical_rewards: 0,
+                top_balance: 0,
+            })
+            .collect();
+        Self {
+            state: Mutex::new(State {
+                seed: config.seed,
+                rng: StdRng::seed_from_u64(config.seed),
+                started: Instant::now(),
+                landing_probability: config.landing_probability,
+                latency: config.latency,
+                config: Config {
+                    base_reward_rate: BASE_REWARD_RATE,
+                    last_reset_at: GENESIS_TIMESTAMP,
+                    min_difficulty: config.min_difficulty as u64,
+                    top_balance: ONE_ORE,
+                },
+                busses,
+                proofs: HashMap::new(),
+                balances: HashMap::new(),
+                pending: VecDeque::new(),
+                statuses: HashMap::new(),
+            }),
+        }
+    }
+}
+
+#[async_trait]
+impl RpcSender for FakeChain {
+    async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+        let mut guard = self.state.lock().unwrap();
+        let state = &mut *guard;
+        state.process_pending();
+        let slot = state.slot();
+        let context = RpcResponseContext {
+            slot,
+            api_version: None,
+        };
+        let value = match request {
+            RpcRequest::GetVersion => json!(RpcVersionInfo {
+                solana_core: "1.18.17".to_string(),
+                feature_set: None,
+            }),
+            RpcRequest::GetSlot => json!(slot),
+            RpcRequest::GetAccountInfo => {
+                let address = parse_pubkey(&params[0])?;
+                json!(Response {
+                    context,
+                    value: state.account(&address),
+                })
+            }
+            RpcRequest::GetMultipleAccounts => {
+                let accounts = params[0]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|address| Ok(state.account(&parse_pubkey(address)?)))
+                    .collect::<ClientResult<Vec<_>>>()?;
+                json!(Response {
+                    context,
+                    value: accounts,
+                })
+            }
+            RpcRequest::GetBalance => {
+                let address = parse_pubkey(&params[0])?;
+                json!(Response {
+                    context,
+                    value: state.balance(&address),
+                })
+            }
+            RpcRequest::GetLatestBlockhash => json!(Response {
+                context,
+                value: RpcBlockhash {
+                    blockhash: hashv(&[b"blockhash", &slot.to_le_bytes()]).to_string(),
+                    last_valid_block_height: slot + 150,
+                },
+            }),
+            RpcRequest::SendTransaction => {
+                let tx = parse_transaction(&params[0])?;
+                let signature = tx.signatures[0];
+                if let Entry::Vacant(entry) = state.statuses.entry(signature) {
+                    entry.insert(None);
+                    if state.rng.gen_bool(state.landing_probability) {
+                        let lands_at = Instant::now() + state.latency;
+                        state.pending.push_back((lands_at, tx));
+                    }
+                }
+                json!(signature.to_string())
+            }
+            RpcRequest::GetSignatureStatuses => {
+                let statuses = params[0]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|signature| {
+                        let signature = signature
+                            .as_str()
+                            .and_then(|s| s.parse::<Signature>().ok())
+                            .ok_or_else(|| invalid_params("signature"))?;
+                        Ok(state.statuses.get(&signature).cloned().flatten())
+                    })
+                    .collect::<ClientResult<Vec<_>>>()?;
+                json!(Response {
+                    context,
+                    value: statuses,
+                })
+            }
+            RpcRequest::SimulateTransaction => {
+                let tx = parse_transaction(&params[0])?;
+                let mut scratch = state.fork();
+                let execution = scratch.execute(&tx);
+                json!(Response {
+                    context,
+                    value: RpcSimulateTransactionResult {
+                        err: execution.result.err(),
+                        logs: Some(execution.logs),
+                        accounts: None,
+                        units_consumed: None,
+                        return_data: None,
+                        inner_instructions: None,
+                    },
+                })
+            }
+            _ => {
+                return Err(ClientErrorKind::Custom(format!(
+                    "{} is not supported by the simulator",
+                    request
+                ))
+                .into())
+            }
+        };
+        Ok(value)
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        RpcTransportStats::default()
+    }
+
+    fn url(&self) -> String {
+        "simulator".to_string()
+    }
+}
+
+impl State {
+    fn now(&self) -> i64 {
+        GENESIS_TIMESTAMP + self.started.elapsed().as_secs() as i64
+    }
+
+    fn slot(&self) -> u64 {
+        (self.started.elapsed().as_millis() / SLOT_MS) as u64
+    }
+
+    /// The challenge for an authority's proof after `hashes` accepted hashes.
+    fn challenge(&self, authority: &Pubkey, hashes: u64) -> [u8; 32] {
+        hashv(&[
+            &self.seed.to_le_bytes(),
+            authority.as_ref(),
+            &hashes.to_le_bytes(),
+        ])
+        .to_bytes()
+    }
+
+    fn balance(&self, address: &Pubkey) -> u64 {
+        self.balances
+            .get(address)
+            .copied()
+            .unwrap_or(INITIAL_SOL_BALANCE)
+    }
+
+    /// Returns the account at an address in the JSON form of a base64-encoded `UiAccount`.
+    fn account(&self, address: &Pubkey) -> Option<Value> {
+        let (owner, data) = if address.eq(&sysvar::clock::ID) {
+            let clock = Clock {
+                slot: self.slot(),
+                unix_timestamp: self.now(),
+                ..Default::default()
+            };
+            (sysvar::ID, bincode::serialize(&clock).unwrap())
+        } else if address.eq(&CONFIG_ADDRESS) {
+            (
+                ore_api::ID,
+                account_data(Config::discriminator(), self.config.to_bytes()),
+            )
+        } else if let Some(i) = BUS_ADDRESSES.iter().position(|bus| bus.eq(address)) {
+            (
+                ore_api::ID,
+                account_data(Bus::discriminator(), self.busses[i].to_bytes()),
+            )
+        } else if let Some(proof) = self.proofs.get(address) {
+            (
+                ore_api::ID,
+                account_data(Proof::discriminator(), proof.to_bytes()),
+            )
+        } else if self.balances.contains_key(address) {
+            (system_program::ID, vec![])
+        } else {
+            return None;
+        };
+        Some(json!({
+            "lamports": self.balance(address),
+            "data": [BASE64_STANDARD.encode(&data), "base64"],
+            "owner": owner.to_string(),
+            "executable": false,
+            "rentEpoch": 0,
+            "space": data.len(),
+        }))
+    }
+
+    /// Copies the ledger state for simulating a transaction without committing it.
+    fn fork(&self) -> State {
+        State {
+            seed: self.seed,
+            rng: self.rng.clone(),
+            started: self.started,
+            landing_probability: self.landing_probability,
+            latency: self.latency,
+            config: self.config,
+            busses: self.busses.clone(),
+            proofs: self.proofs.clone(),
+            balances: self.balances.clone(),
+            pending: VecDeque::new(),
+            statuses: HashMap::new(),
+        }
+    }
+
+    /// Executes every sent transaction whose latency has elapsed.
+    fn process_pending(&mut self) {
+        while let Some((lands_at, _)) = self.pending.front() {
+            if lands_at.gt(&Instant::now()) {
+                break;
+            }
+            let (_, tx) = self.pending.pop_front().unwrap();
+            let execution = self.execute(&tx);
+            let err = execution.result.clone().err();
+            let status = TransactionStatus {
+                slot: self.slot(),
+                confirmations: None,
+                status: execution.result,
+                err,
+                confirmation_status: Some(TransactionConfirmationStatus::Confirmed),
+            };
+            self.statuses.insert(tx.signatures[0], Some(status));
+        }
+    }
+
+    /// Charges fees and applies a transaction's instructions. State changes are only kept
+    /// if every instruction succeeds.
+    fn execute(&mut self, tx: &Transaction) -> Execution {
+        let mut logs = vec![];
+        let keys = &tx.message.account_keys;
+        let payer = keys[0];
+
+        // Charge fees
+        let mut cu_limit = 0u64;
+        let mut cu_price = 0u64;
+        for ix in tx.message.instructions.iter() {
+            if keys[ix.program_id_index as usize].eq(&compute_budget::ID) {
+                match ix.data.first() {
+                    Some(2) if ix.data.len().ge(&5) => {
+                        cu_limit = u32::from_le_bytes(ix.data[1..5].try_into().unwrap()) as u64
+                    }
+                    Some(3) if ix.data.len().ge(&9) => {
+                        cu_price = u64::from_le_bytes(ix.data[1..9].try_into().unwrap())
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let fee = SIGNATURE_FEE.saturating_mul(tx.signatures.len() as u64)
+            + (cu_limit as u128 * cu_price as u128 / 1_000_000) as u64;
+        let balance = self.balance(&payer);
+        if balance.lt(&fee) {
+            return Execution {
+                result: Err(TransactionError::InsufficientFundsForFee),
+                logs,
+            };
+        }
+        self.balances.insert(payer, balance - fee);
+
+        // Apply instructions atomically
+        let mut scratch = self.fork();
+        for (i, ix) in tx.message.instructions.iter().enumerate() {
+            let program_id = keys[ix.program_id_index as usize];
+            let accounts: Vec<Pubkey> = ix.accounts.iter().map(|a| keys[*a as usize]).collect();
+            logs.push(format!("Program {} invoke [1]", program_id));
+            let result = if program_id.eq(&ore_api::ID) {
+                scratch.execute_ore(&accounts, &ix.data)
+            } else if program_id.eq(&system_program::ID) {
+                scratch.execute_system(&accounts, &ix.data)
+            } else if program_id.eq(&compute_budget::ID) || program_id.eq(&NOOP_PROGRAM_ID) {
+                Ok(())
+            } else {
+                Err(InstructionError::UnsupportedProgramId)
+            };
+            if let Err(err) = result {
+                logs.push(format!("Program {} failed: {}", program_id, err));
+                return Execution {
+                    result: Err(TransactionError::InstructionError(i as u8, err)),
+                    logs,
+                };
+            }
+            logs.push(format!("Program {} success", program_id));
+        }
+        self.busses = scratch.busses;
+        self.config = scratch.config;
+        self.proofs = scratch.proofs;
+        self.balances = scratch.balances;
+        Execution {
+            result: Ok(()),
+            logs,
+        }
+    }
+
+    fn execute_system(&mut self, accounts: &[Pubkey], data: &[u8]) -> Result<(), InstructionError> {
+        let Ok(SystemInstruction::Transfer { lamports }) = bincode::deserialize(data) else {
+            return Err(InstructionError::InvalidInstructionData);
+        };
+        let from = self.balance(&accounts[0]);
+        if from.lt(&lamports) {
+            return Err(InstructionError::InsufficientFunds);
+        }
+        self.balances.insert(accounts[0], from - lamports);
+        let to = self.balances.get(&accounts[1]).copied().unwrap_or(0);
+        self.balances.insert(accounts[1], to + lamports);
+        Ok(())
+    }
+
+    fn execute_ore(&mut self, accounts: &[Pubkey], data: &[u8]) -> Result<(), InstructionError> {
+        let Some(Ok(ix)) = data.first().map(|tag| OreInstruction::try_from(*tag)) else {
+            return Err(InstructionError::InvalidInstructionData);
+        };
+        let now = self.now();
+        match ix {
+            OreInstruction::Open => {
+                let signer = accounts[0];
+                let address = proof_pubkey(signer);
+                if self.proofs.contains_key(&address) {
+                    return Err(InstructionError::AccountAlreadyInitialized);
+                }
+                let proof = Proof {
+                    authority: signer,
+                    balance: 0,
+                    challenge: self.challenge(&signer, 0),
+                    last_hash: [0; 32],
+                    last_hash_at: now,
+                    last_stake_at: now,
+                    miner: accounts[1],
+                    total_hashes: 0,
+                    total_rewards: 0,
+                };
+                self.proofs.insert(address, proof);
+                Ok(())
+            }
+            OreInstruction::Reset => {
+                if self
+                    .config
+                    .last_reset_at
+                    .saturating_add(EPOCH_DURATION)
+                    .gt(&now)
+                {
+                    return Ok(());
+                }
+                self.config.last_reset_at = now;
+                for bus in self.busses.iter_mut() {
+                    bus.rewards = BUS_EPOCH_REWARDS;
+                    bus.theoretical_rewards = 0;
+                    bus.top_balance = 0;
+                }
+                Ok(())
+            }
+            OreInstruction::Mine => self.execute_mine(accounts, &data[1..], now),
+            _ => Err(InstructionError::InvalidInstructionData),
+        }
+    }
+
+    fn execute_mine(
+        &mut self,
+        accounts: &[Pubkey],
+        data: &[u8],
+        now: i64,
+    ) -> Result<(), InstructionError> {
+        let args =
+            MineArgs::try_from_bytes(data).map_err(|_| InstructionError::InvalidInstructionData)?;
+        let signer = accounts[0];
+        let Some(bus_index) = BUS_ADDRESSES.iter().position(|bus| bus.eq(&accounts[1])) else {
+            return Err(InstructionError::InvalidAccountData);
+        };
+        let Some(mut proof) = self.proofs.get(&accounts[3]).copied() else {
+            return Err(InstructionError::UninitializedAccount);
+        };
+        if proof.miner.ne(&signer) {
+            return Err(ore_error(OreError::AuthFailed));
+        }
+
+        // Validate the epoch and submission time
+        if self
+            .config
+            .last_reset_at
+            .saturating_add(EPOCH_DURATION)
+            .le(&now)
+        {
+            return Err(ore_error(OreError::NeedsReset));
+        }
+        if now.lt(&proof
+            .last_hash_at
+            .saturating_add(ONE_MINUTE)
+            .saturating_sub(TOLERANCE))
+        {
+            return Err(ore_error(OreError::Spam));
+        }
+
+        // Validate the hash
+        let solution = Solution::new(args.digest, args.nonce);
+        if !solution.is_valid(&proof.challenge) {
+            return Err(ore_error(OreError::HashInvalid));
+        }
+        let hash = solution.to_hash();
+        let difficulty = hash.difficulty();
+        if difficulty.lt(&(self.config.min_difficulty as u32)) {
+            return Err(ore_error(OreError::HashTooEasy));
+        }
+
+        // Pay out the reward, capped by the bus balance
+        let bus = &mut self.busses[bus_index];
+        let reward = calculate_reward(&self.config, &proof, difficulty, now).min(bus.rewards);
+        bus.rewards -= reward;
+        bus.theoretical_rewards = bus.theoretical_rewards.saturating_add(reward);
+        proof.balance = proof.balance.saturating_add(reward);
+        proof.last_hash = hash.h;
+        proof.last_hash_at = now;
+        proof.total_hashes = proof.total_hashes.saturating_add(1);
+        proof.challenge = self.challenge(&proof.authority, proof.total_hashes);
+        proof.total_rewards = proof.total_rewards.saturating_add(reward);
+        self.proofs.insert(accounts[3], proof);
+        Ok(())
+    }
+}
+
+fn ore_error(err: OreError) -> InstructionError {
+    InstructionError::Custom(err.into())
+}
+
+fn account_data(discriminator: u8, bytes: &[u8]) -> Vec<u8> {
+    let mut data = vec![0; 8];
+    data[0] = discriminator;
+    data.extend_from_slice(bytes);
+    data
+}
+
+fn invalid_params(what: &str) -> solana_client::client_error::ClientError {
+    ClientErrorKind::Custom(format!("Invalid {} param", what)).into()
+}
+
+fn parse_pubkey(value: &Value) -> ClientResult<Pubkey> {
+    value
+        .as_str()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| invalid_params("pubkey"))
+}
+
+fn parse_transaction(value: &Value) -> ClientResult<Transaction> {
+    value
+        .as_str()
+        .and_then(|s| BASE64_STANDARD.decode(s).ok())
+        .and_then(|bytes| bincode::deserialize(&bytes).ok())
+        .ok_or_else(|| invalid_params("transaction"))
+}
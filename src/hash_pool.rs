@@ -0,0 +1,373 @@
+use std::{
+    ops::Range,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use drillx::{equix, Hash, Solution};
+
+use colored::*;
+
+use crate::{affinity::Placement, throttle::Throttle};
+
+/// How often a worker checks the clock, in hashes.
+const CLOCK_INTERVAL: u64 = 100;
+
+/// How long an inactive worker waits before checking whether it may hash again.
+const IDLE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A unit of hashing work. The pool splits the nonce range evenly across machines and
+/// then across its workers.
+#[derive(Clone, Debug)]
+pub struct HashJob {
+    pub challenge: [u8; 32],
+    pub nonces: Range<u64>,
+    pub cutoff: Instant,
+    /// When to stop even if the min difficulty has not been reached.
+    pub hard_cutoff: Option<Instant>,
+    pub min_difficulty: u32,
+    /// Stops every worker once a hash reaches this and the min difficulty.
+    pub target_difficulty: Option<u32>,
+}
+
+/// This machine's share of the nonce space, when several machines mine the same
+/// authority. Each machine scans a disjoint slice of every job's nonce range.
+#[derive(Clone, Copy, Debug)]
+pub struct NoncePartition {
+    index: u64,
+    count: u64,
+}
+
+impl NoncePartition {
+    pub fn new(index: u64, count: u64) -> Result<Self, String> {
+        if count.eq(&0) {
+            return Err("Machine count must be at least 1".to_string());
+        }
+        if index.ge(&count) {
+            return Err(format!(
+                "Machine index ({}) must be less than machine count ({})",
+                index, count
+            ));
+        }
+        Ok(Self { index, count })
+    }
+
+    /// The slice of a nonce range belonging to this machine. The slices of all machines
+    /// are disjoint and together cover the range.
+    pub fn split(&self, nonces: &Range<u64>) -> Range<u64> {
+        let len = nonces.end.saturating_sub(nonces.start);
+        let (span, extra) = (len / self.count, len % self.count);
+        let start = nonces.start + span * self.index + self.index.min(extra);
+        start..start + span + u64::from(self.index.lt(&extra))
+    }
+}
+
+/// The best hash found across all workers for a job, with each worker's hash count.
+#[derive(Clone, Debug, Default)]
+pub struct BestHash {
+    pub nonce: u64,
+    pub difficulty: u32,
+    pub digest: [u8; 16],
+    pub hashes: Vec<u64>,
+    /// Whether a worker on the latest job stopped at the hard cutoff, before its own best
+    /// hash reached the min difficulty.
+    pub hard_stopped: bool,
+}
+
+impl BestHash {
+    pub fn solution(&self) -> Solution {
+        Solution::new(self.digest, self.nonce.to_le_bytes())
+    }
+
+    /// Checks the solution against a challenge the way the program will, so a hash that
+    /// would be rejected is not paid for.
+    pub fn verify(&self, challenge: &[u8; 32], min_difficulty: u32) -> Result<(), String> {
+        let solution = self.solution();
+        if !solution.is_valid(challenge) {
+            return Err("Solution does not match the challenge".to_string());
+        }
+        let difficulty = solution.to_hash().difficulty();
+        if difficulty.lt(&min_difficulty) {
+            return Err(format!(
+                "Difficulty {} is below the minimum of {}",
+                difficulty, min_difficulty
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn total_hashes(&self) -> u64 {
+        self.hashes.iter().sum()
+    }
+
+    /// Combines the results of another job over the same challenge, keeping the harder hash.
+    pub fn merge(&mut self, other: BestHash) {
+        if other.difficulty.gt(&self.difficulty) {
+            self.nonce = other.nonce;
+            self.difficulty = other.difficulty;
+            self.digest = other.digest;
+        }
+        self.hard_stopped = other.hard_stopped;
+        self.hashes
+            .resize(self.hashes.len().max(other.hashes.len()), 0);
+        for (total, hashes) in self.hashes.iter_mut().zip(other.hashes) {
+            *total += hashes;
+        }
+    }
+}
+
+/// The best hash a worker found for a job.
+struct HashResult {
+    worker: usize,
+    nonce: u64,
+    difficulty: u32,
+    digest: [u8; 16],
+    hashes: u64,
+    hard_stopped: bool,
+}
+
+struct Worker {
+    jobs: Option<Sender<HashJob>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+/// A long-lived set of hashing threads. Each worker keeps its own solver memory
+/// warm across rounds and receives jobs over a channel.
+pub struct HashPool {
+    workers: Vec<Worker>,
+    partition: NoncePartition,
+    results: Receiver<HashResult>,
+    stop: Arc<AtomicBool>,
+    throttle: Throttle,
+}
+
+impl HashPool {
+    pub fn new(threads: u64, partition: NoncePartition, placement: Placement) -> Self {
+        let (results_tx, results) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let throttle = Throttle::new(threads);
+        let workers = (0..threads as usize)
+            .map(|id| {
+                let (jobs_tx, jobs) = mpsc::channel::<HashJob>();
+                let results_tx = results_tx.clone();
+                let stop = stop.clone();
+                let throttle = throttle.clone();
+                let placement = placement.clone();
+                let handle = std::thread::spawn(move || {
+                    if let Err(err) = placement.apply(id) {
+                        println!(
+                            "{} Could not place hashing thread {}: {}",
+                            "WARNING".bold().yellow(),
+                            id,
+                            err
+                        );
+                    }
+                    let mut memory = equix::SolverMemory::new();
+                    while let Ok(job) = jobs.recv() {
+                        let result = run_job(id, &job, &stop, &throttle, &mut memory);
+                        if results_tx.send(result).is_err() {
+                            break;
+                        }
+                    }
+                });
+                Worker {
+                    jobs: Some(jobs_tx),
+                    handle: Some(handle),
+                }
+            })
+            .collect();
+        Self {
+            workers,
+            partition,
+            results,
+            stop,
+            throttle,
+        }
+    }
+
+    pub fn threads(&self) -> u64 {
+        self.workers.len() as u64
+    }
+
+    /// The CPU limit applied to every worker.
+    pub fn throttle(&self) -> &Throttle {
+        &self.throttle
+    }
+
+    /// Takes this machine's share of the job's nonce range, splits it evenly across
+    /// workers, and starts hashing. The first workers take one extra nonce each when the
+    /// range does not divide evenly.
+    pub fn dispatch(&self, job: HashJob) {
+        self.stop.store(false, Ordering::Relaxed);
+        let nonces = self.partition.split(&job.nonces);
+        let len = nonces.end - nonces.start;
+        let threads = self.threads().max(1);
+        let (span, extra) = (len / threads, len % threads);
+        for (i, worker) in self.workers.iter().enumerate() {
+            let i = i as u64;
+            let start = nonces.start + span * i + i.min(extra);
+            let end = start + span + u64::from(i.lt(&extra));
+            let job = HashJob {
+                nonces: start..end,
+                ..job.clone()
+            };
+            if let Some(jobs) = &worker.jobs {
+                jobs.send(job).ok();
+            }
+        }
+    }
+
+    /// Waits for every worker to report on the dispatched job, calling `tick`
+    /// periodically while hashing is in progress.
+    pub fn collect(&self, mut tick: impl FnMut()) -> BestHash {
+        let mut best = BestHash {
+            hashes: vec![0; self.workers.len()],
+            ..Default::default()
+        };
+        let mut pending = self.workers.len();
+        while pending.gt(&0) {
+            match self.results.recv_timeout(Duration::from_millis(500)) {
+                Ok(result) => {
+                    pending -= 1;
+                    best.hashes[result.worker] = result.hashes;
+                    best.hard_stopped |= result.hard_stopped;
+                    if result.difficulty.gt(&best.difficulty) {
+                        best.nonce = result.nonce;
+                        best.difficulty = result.difficulty;
+                        best.digest = result.digest;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => tick(),
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        best
+    }
+}
+
+impl Drop for HashPool {
+    fn drop(&mut self) {
+        // Close job channels so workers exit their receive loops
+        for worker in self.workers.iter_mut() {
+            worker.jobs.take();
+        }
+        for worker in self.workers.iter_mut() {
+            if let Some(handle) = worker.handle.take() {
+                handle.join().ok();
+            }
+        }
+    }
+}
+
+fn run_job(
+    worker: usize,
+    job: &HashJob,
+    stop: &AtomicBool,
+    throttle: &Throttle,
+    memory: &mut equix::SolverMemory,
+) -> HashResult {
+    let mut nonce = job.nonces.start;
+    let mut best_nonce = nonce;
+    let mut best_difficulty = 0;
+    let mut best_hash = Hash::default();
+    let mut hard_stopped = false;
+    while nonce < job.nonces.end {
+        // Idle while throttled, leaving the job to active workers
+        if !throttle.is_active(worker) {
+            std::thread::sleep(IDLE_INTERVAL);
+            let now = Instant::now();
+            if stop.load(Ordering::Relaxed)
+                || now.ge(&job.cutoff)
+                || job.hard_cutoff.is_some_and(|t| now.ge(&t))
+            {
+                break;
+            }
+            continue;
+        }
+
+        // Create hash
+        let timer = Instant::now();
+        if let Ok(hx) = drillx::hash_with_memory(memory, &job.challenge, &nonce.to_le_bytes()) {
+            let difficulty = hx.difficulty();
+            if difficulty.gt(&best_difficulty) {
+                best_nonce = nonce;
+                best_difficulty = difficulty;
+                best_hash = hx;
+
+                // Stop every worker once the target difficulty has been reached. A hash
+                // below the min difficulty would be rejected, so keep going until then.
+                if job
+                    .target_difficulty
+                    .is_some_and(|t| difficulty.ge(&t.max(job.min_difficulty)))
+                {
+                    stop.store(true, Ordering::Relaxed);
+                }
+            }
+        }
+
+        // Increment nonce and rest, if limited
+        nonce += 1;
+        let paced = throttle.pace(timer.elapsed());
+
+        // Exit if another worker reached the target difficulty
+        if stop.load(Ordering::Relaxed) {
+            break;
+        }
+
+        // Exit if time has elapsed and the program would accept the best hash, or the
+        // hard cutoff has passed. Throttled workers check after every rest.
+        if paced || nonce % CLOCK_INTERVAL == 0 {
+            let now = Instant::now();
+            if now.ge(&job.cutoff) && best_difficulty.ge(&job.min_difficulty) {
+                break;
+            }
+            if job.hard_cutoff.is_some_and(|t| now.ge(&t)) {
+                hard_stopped = true;
+                break;
+            }
+        }
+    }
+
+    HashResult {
+        worker,
+        nonce: best_nonce,
+        difficulty: best_difficulty,
+        digest: best_hash.d,
+        hashes: nonce - job.nonces.start,
+        hard_stopped,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partitions_are_disjoint_and_cover_the_range() {
+        for nonces in [0..u64::MAX, 0..10, 5..6, 7..7, 1000..1003] {
+            for count in [1, 2, 3, 7, 16] {
+                let slices: Vec<Range<u64>> = (0..count)
+                    .map(|index| NoncePartition::new(index, count).unwrap().split(&nonces))
+                    .collect();
+                assert_eq!(slices.first().unwrap().start, nonces.start);
+                assert_eq!(slices.last().unwrap().end, nonces.end);
+                for pair in slices.windows(2) {
+                    assert_eq!(pair[0].end, pair[1].start, "{:?} / {}", nonces, count);
+                    assert!(pair[0].start.le(&pair[0].end));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_partition() {
+        assert!(NoncePartition::new(0, 0).is_err());
+        assert!(NoncePartition::new(2, 2).is_err());
+        assert!(NoncePartition::new(1, 2).is_ok());
+    }
+}
@@ -1,10 +1,13 @@
+use std::time::Duration;
+
 use crate::send_and_confirm::ComputeBudget;
 
-use crate::Miner;
+use crate::{metrics::METRICS, profitability::priority_fee_cost, send_and_confirm::Sender, Miner};
 use colored::Colorize;
 use rand::Rng;
 use serde::{de, Deserialize};
 use serde_json::{json, Value};
+use solana_client::client_error::{ClientError, ClientErrorKind};
 use solana_program::pubkey::Pubkey;
 use solana_program::{
     instruction::Instruction,
@@ -12,12 +15,19 @@ use solana_program::{
 };
 
 use solana_sdk::{
-    compute_budget::ComputeBudgetInstruction,
     pubkey,
     signature::{Signature, Signer},
     transaction::Transaction,
 };
-use solana_transaction_status::{Encodable, EncodedTransaction, UiTransactionEncoding};
+use solana_transaction_status::{
+    Encodable, EncodedTransaction, TransactionConfirmationStatus, UiTransactionEncoding,
+};
+
+/// The tip paid to the Jito block engine with every bundle, in lamports.
+pub const JITO_TIP: u64 = 1_000_000;
+
+const CONFIRM_RETRIES: usize = 15;
+const CONFIRM_DELAY: u64 = 2000;
 
 pub const JITO_RECIPIENTS: [Pubkey; 8] = [
     pubkey!("96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5"),
@@ -49,13 +59,14 @@ impl Miner {
         &self,
         ixs: &[Instruction],
         compute_budget: ComputeBudget,
-        _skip_confirm: bool,
+        skip_confirm: bool,
     ) -> eyre::Result<Signature> {
         let signer = self.signer();
         let client = self.rpc_client.clone();
 
         // Return error, if balance is zero
         if let Ok(balance) = client.get_balance(&signer.pubkey()).await {
+            METRICS.set_sol_balance(balance);
             if balance <= sol_to_lamports(crate::send_and_confirm::MIN_SOL_BALANCE) {
                 panic!(
                     "{} Insufficient balance: {} SOL\nPlease top up with at least {} SOL",
@@ -66,33 +77,8 @@ impl Miner {
             }
         }
 
-        // Set compute units
-        let mut final_ixs = vec![];
-        match compute_budget {
-            ComputeBudget::Dynamic => {
-                // TODO simulate
-                final_ixs.push(ComputeBudgetInstruction::set_compute_unit_limit(1_400_000))
-            }
-            ComputeBudget::Fixed(cus) => {
-                final_ixs.push(ComputeBudgetInstruction::set_compute_unit_limit(cus))
-            }
-        }
-        final_ixs.push(ComputeBudgetInstruction::set_compute_unit_price(
-            self.priority_fee,
-        ));
-        final_ixs.extend_from_slice(ixs);
-        final_ixs.push(build_bribe_ix(&signer.pubkey(), 1_000_000));
-
-        // Build tx
-        let mut tx = Transaction::new_with_payer(&final_ixs, Some(&signer.pubkey()));
-
-        // Sign tx
-        let (hash, _slot) = client
-            .get_latest_blockhash_with_commitment(self.rpc_client.commitment())
-            .await
-            .unwrap();
-
-        tx.sign(&[&signer], hash);
+        // Build and sign tx
+        let (tx, cus) = self.build_jito_tx(ixs, compute_budget).await;
 
         let mut bundle = Vec::with_capacity(5);
         bundle.push(tx);
@@ -112,10 +98,60 @@ impl Miner {
             })
             .collect::<Vec<_>>();
 
-        make_jito_request("sendBundle", json!([bundle])).await?;
+        if let Err(err) = make_jito_request::<Value>("sendBundle", json!([bundle])).await {
+            METRICS.record_failed(Sender::Jito);
+            return Err(err);
+        }
+        METRICS.record_sent(Sender::Jito);
 
         println!("signature:{:?}", signature);
-        Ok(signature)
+        if skip_confirm {
+            return Ok(signature);
+        }
+
+        // Confirm the bundle landed
+        for _ in 0..CONFIRM_RETRIES {
+            tokio::time::sleep(Duration::from_millis(CONFIRM_DELAY)).await;
+            let Ok(signature_statuses) = client.get_signature_statuses(&[signature]).await else {
+                continue;
+            };
+            for status in signature_statuses.value.into_iter().flatten() {
+                if let Some(err) = status.err {
+                    // Fees are charged, but the tip transfer is rolled back with the rest
+                    METRICS.record_failed(Sender::Jito);
+                    METRICS.record_spend(priority_fee_cost(self.priority_fee, cus), 0);
+                    return Err(ClientError {
+                        request: None,
+                        kind: ClientErrorKind::TransactionError(err),
+                    }
+                    .into());
+                }
+                if let Some(
+                    TransactionConfirmationStatus::Confirmed
+                    | TransactionConfirmationStatus::Finalized,
+                ) = status.confirmation_status
+                {
+                    METRICS.record_landed(Sender::Jito);
+                    METRICS.record_spend(priority_fee_cost(self.priority_fee, cus), JITO_TIP);
+                    println!("{} {}", "OK".bold().green(), signature);
+                    return Ok(signature);
+                }
+            }
+        }
+        METRICS.record_failed(Sender::Jito);
+        eyre::bail!("bundle not confirmed: {signature}")
+    }
+
+    /// Builds a signed transaction with compute budget instructions and a Jito tip.
+    /// Returns the transaction and its compute unit limit.
+    pub async fn build_jito_tx(
+        &self,
+        ixs: &[Instruction],
+        compute_budget: ComputeBudget,
+    ) -> (Transaction, u32) {
+        let mut final_ixs = ixs.to_vec();
+        final_ixs.push(build_bribe_ix(&self.signer().pubkey(), JITO_TIP));
+        self.build_tx(&final_ixs, compute_budget).await
     }
 }
 
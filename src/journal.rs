@@ -0,0 +1,93 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+};
+
+use colored::*;
+use serde::Serialize;
+use solana_program::pubkey::Pubkey;
+
+use crate::hash_pool::BestHash;
+
+/// What became of a round's best hash.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Landed,
+    Failed,
+    Skipped,
+    /// Kept unsubmitted to mine the same challenge for a better hash.
+    Deferred,
+    Simulated,
+    Abandoned,
+}
+
+/// A single line of the mining journal, describing one round.
+#[derive(Debug, Serialize)]
+pub struct RoundEntry {
+    pub timestamp: String,
+    pub authority: String,
+    pub challenge: String,
+    pub nonce: u64,
+    pub difficulty: u32,
+    pub hashes: Vec<u64>,
+    pub bus: Option<String>,
+    pub reset: bool,
+    pub hard_deadline_passed: bool,
+    pub signature: Option<String>,
+    pub outcome: Outcome,
+    pub error: Option<String>,
+    pub balance_change: i64,
+    pub reward: Option<u64>,
+}
+
+impl RoundEntry {
+    pub fn new(
+        authority: Pubkey,
+        challenge: [u8; 32],
+        best: &BestHash,
+        balance_change: i64,
+    ) -> Self {
+        Self {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            authority: authority.to_string(),
+            challenge: bs58::encode(challenge).into_string(),
+            nonce: best.nonce,
+            difficulty: best.difficulty,
+            hashes: best.hashes.clone(),
+            bus: None,
+            reset: false,
+            hard_deadline_passed: false,
+            signature: None,
+            outcome: Outcome::Skipped,
+            error: None,
+            balance_change,
+            reward: None,
+        }
+    }
+}
+
+/// An append-only JSONL file of round entries.
+pub struct Journal {
+    file: File,
+}
+
+impl Journal {
+    pub fn open(path: &str) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file })
+    }
+
+    pub fn record(&mut self, entry: &RoundEntry) {
+        let res = serde_json::to_string(entry)
+            .map_err(io::Error::from)
+            .and_then(|line| writeln!(self.file, "{}", line));
+        if let Err(err) = res {
+            println!(
+                "{} Failed to write journal: {}",
+                "WARNING".bold().yellow(),
+                err
+            );
+        }
+    }
+}
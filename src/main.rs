@@ -1,21 +1,39 @@
+mod adaptive_buffer;
+mod affinity;
 mod args;
 mod balance;
 mod benchmark;
+mod bus_strategy;
 mod busses;
 mod claim;
 mod close;
 mod config;
 mod cu_limits;
+mod fake_chain;
+mod hash_pool;
 #[cfg(feature = "admin")]
 mod initialize;
+mod jito_send_and_confirm;
+mod journal;
+mod metrics;
 mod mine;
 mod open;
+mod pool;
+mod profitability;
+mod reset_policy;
 mod rewards;
+mod schedule;
 mod send_and_confirm;
+mod session;
+mod shutdown;
+mod simulate;
 mod stake;
+mod thermal;
+mod throttle;
+mod time_sync;
+mod timezone;
 mod upgrade;
 mod utils;
-mod jito_send_and_confirm;
 
 use std::sync::Arc;
 
@@ -59,6 +77,12 @@ enum Commands {
     #[command(about = "Fetch the current reward rate for each difficulty level")]
     Rewards(RewardsArgs),
 
+    #[command(
+        about = "Mine against an offline simulated chain",
+        long_about = "Mine against an offline simulated chain. The simulated clock follows wall time and hashing runs at the machine's real speed, so a simulated hour takes an hour, and the difficulties found, and with them the rewards, vary between runs with the same seed."
+    )]
+    Simulate(SimulateArgs),
+
     #[command(about = "Stake to earn a rewards multiplier")]
     Stake(StakeArgs),
 
@@ -164,6 +188,9 @@ async fn main() {
         Commands::Rewards(_) => {
             miner.rewards().await;
         }
+        Commands::Simulate(args) => {
+            miner.simulate(args).await;
+        }
         Commands::Stake(args) => {
             miner.stake(args).await;
         }
@@ -190,6 +217,11 @@ impl Miner {
         }
     }
 
+    /// Returns a miner that signs with another key, sharing this miner's RPC client.
+    pub fn with_private_key(&self, private_key: Option<String>) -> Self {
+        Self::new(self.rpc_client.clone(), self.priority_fee, private_key)
+    }
+
     pub fn signer(&self) -> Keypair {
         match self.private_key.clone() {
             Some(key) => Keypair::from_base58_string(&key),
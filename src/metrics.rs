@@ -0,0 +1,242 @@
+use std::{
+    fmt::Write as _,
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use solana_program::native_token::lamports_to_sol;
+
+use crate::{profitability::SIGNATURE_FEE, send_and_confirm::Sender, utils::amount_u64_to_f64};
+
+/// Process-wide miner metrics, exported in the Prometheus text format.
+pub static METRICS: Metrics = Metrics::new();
+
+/// How long a metrics client may take to send its request or read the response.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct SubmissionMetrics {
+    sent: AtomicU64,
+    landed: AtomicU64,
+    failed: AtomicU64,
+}
+
+impl SubmissionMetrics {
+    const fn new() -> Self {
+        Self {
+            sent: AtomicU64::new(0),
+            landed: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+        }
+    }
+}
+
+pub struct Metrics {
+    hashes: AtomicU64,
+    hashrate: AtomicU64,
+    best_difficulty: AtomicU64,
+    submissions: [SubmissionMetrics; 2],
+    sol_balance: AtomicU64,
+    stake_balance: AtomicU64,
+    signature_fee_spend: AtomicU64,
+    priority_fee_spend: AtomicU64,
+    tip_spend: AtomicU64,
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        Self {
+            hashes: AtomicU64::new(0),
+            hashrate: AtomicU64::new(0),
+            best_difficulty: AtomicU64::new(0),
+            submissions: [SubmissionMetrics::new(), SubmissionMetrics::new()],
+            sol_balance: AtomicU64::new(0),
+            stake_balance: AtomicU64::new(0),
+            signature_fee_spend: AtomicU64::new(0),
+            priority_fee_spend: AtomicU64::new(0),
+            tip_spend: AtomicU64::new(0),
+        }
+    }
+
+    /// Records the outcome of one round of hashing.
+    pub fn record_round(&self, hashes: u64, secs: f64, best_difficulty: u32) {
+        self.hashes.fetch_add(hashes, Ordering::Relaxed);
+        let hashrate = if secs.gt(&0.0) {
+            hashes as f64 / secs
+        } else {
+            0.0
+        };
+        self.hashrate.store(hashrate.to_bits(), Ordering::Relaxed);
+        self.best_difficulty
+            .store(best_difficulty as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_sent(&self, sender: Sender) {
+        self.submissions[sender as usize]
+            .sent
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_landed(&self, sender: Sender) {
+        self.submissions[sender as usize]
+            .landed
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_failed(&self, sender: Sender) {
+        self.submissions[sender as usize]
+            .failed
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records fees paid by a processed transaction, landed or failed, in lamports. Every
+    /// processed transaction pays the signature fee.
+    pub fn record_spend(&self, priority_fee: u64, tip: u64) {
+        self.signature_fee_spend
+            .fetch_add(SIGNATURE_FEE, Ordering::Relaxed);
+        self.priority_fee_spend
+            .fetch_add(priority_fee, Ordering::Relaxed);
+        self.tip_spend.fetch_add(tip, Ordering::Relaxed);
+    }
+
+    /// The total spent on transaction fees and tips so far, in lamports.
+    pub fn spend(&self) -> u64 {
+        self.signature_fee_spend.load(Ordering::Relaxed)
+            + self.priority_fee_spend.load(Ordering::Relaxed)
+            + self.tip_spend.load(Ordering::Relaxed)
+    }
+
+    pub fn set_sol_balance(&self, lamports: u64) {
+        self.sol_balance.store(lamports, Ordering::Relaxed);
+    }
+
+    pub fn set_stake_balance(&self, grains: u64) {
+        self.stake_balance.store(grains, Ordering::Relaxed);
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let load = |v: &AtomicU64| v.load(Ordering::Relaxed);
+        write_metric(
+            &mut out,
+            "ore_hashes_total",
+            "counter",
+            "Total hashes computed",
+            load(&self.hashes) as f64,
+        );
+        write_metric(
+            &mut out,
+            "ore_hashrate",
+            "gauge",
+            "Hashes per second over the last round",
+            f64::from_bits(load(&self.hashrate)),
+        );
+        write_metric(
+            &mut out,
+            "ore_best_difficulty",
+            "gauge",
+            "Best difficulty found in the last round",
+            load(&self.best_difficulty) as f64,
+        );
+        for (i, (name, help)) in [
+            ("ore_submissions_sent_total", "Transactions sent"),
+            ("ore_submissions_landed_total", "Transactions confirmed"),
+            (
+                "ore_submissions_failed_total",
+                "Transactions that failed or were not confirmed",
+            ),
+        ]
+        .iter()
+        .enumerate()
+        {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            for sender in Sender::ALL {
+                let m = &self.submissions[sender as usize];
+                let value = [&m.sent, &m.landed, &m.failed][i];
+                let _ = writeln!(
+                    out,
+                    "{}{{sender=\"{}\"}} {}",
+                    name,
+                    sender.label(),
+                    load(value)
+                );
+            }
+        }
+        write_metric(
+            &mut out,
+            "ore_sol_balance",
+            "gauge",
+            "SOL balance of the signer",
+            lamports_to_sol(load(&self.sol_balance)),
+        );
+        write_metric(
+            &mut out,
+            "ore_stake_balance",
+            "gauge",
+            "ORE staked in the proof account",
+            amount_u64_to_f64(load(&self.stake_balance)),
+        );
+        write_metric(
+            &mut out,
+            "ore_signature_fee_spend_sol_total",
+            "counter",
+            "SOL spent on signature fees",
+            lamports_to_sol(load(&self.signature_fee_spend)),
+        );
+        write_metric(
+            &mut out,
+            "ore_priority_fee_spend_sol_total",
+            "counter",
+            "SOL spent on priority fees",
+            lamports_to_sol(load(&self.priority_fee_spend)),
+        );
+        write_metric(
+            &mut out,
+            "ore_tip_spend_sol_total",
+            "counter",
+            "SOL spent on Jito tips",
+            lamports_to_sol(load(&self.tip_spend)),
+        );
+        out
+    }
+}
+
+fn write_metric(out: &mut String, name: &str, kind: &str, help: &str, value: f64) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+/// Serves metrics over HTTP on a background thread.
+pub fn serve(addr: SocketAddr) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            handle(stream).ok();
+        }
+    });
+    Ok(())
+}
+
+fn handle(mut stream: TcpStream) -> io::Result<()> {
+    // Drop clients that stall, so they cannot block the next scrape
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+    let (status, body) = match path {
+        "/metrics" | "/" => ("200 OK", METRICS.render()),
+        _ => ("404 Not Found", String::new()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
@@ -1,160 +1,1137 @@
-use std::{sync::Arc, time::Instant};
+use std::{
+    str::FromStr,
+    time::{Duration, Instant},
+};
 
+use chrono::Utc;
+use clap::ValueEnum;
 use colored::*;
-use drillx::{
-    equix::{self},
-    Hash, Solution,
-};
+use drillx::Solution;
 use ore_api::{
-    consts::{BUS_ADDRESSES, BUS_COUNT, EPOCH_DURATION},
-    state::{Config, Proof},
+    consts::{BUS_ADDRESSES, EPOCH_DURATION, ONE_MINUTE, TOLERANCE},
+    state::{Bus, Config, Proof},
+};
+use rand::{rngs::StdRng, SeedableRng};
+use solana_client::client_error::ClientError;
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    native_token::lamports_to_sol,
+    pubkey::Pubkey,
 };
-use rand::Rng;
-use solana_program::pubkey::Pubkey;
 use solana_rpc_client::spinner;
-use solana_sdk::signer::Signer;
+use solana_sdk::{
+    signature::{Keypair, Signature},
+    signer::Signer,
+    transaction::TransactionError,
+};
+use tokio::task::JoinHandle;
 
 use crate::{
+    adaptive_buffer::AdaptiveBuffer,
     args::MineArgs,
-    send_and_confirm::ComputeBudget,
-    utils::{amount_u64_to_string, get_clock, get_config, get_proof_with_authority, proof_pubkey},
+    benchmark::Threads,
+    bus_strategy::BusStrategy,
+    hash_pool::{BestHash, HashJob, HashPool, NoncePartition},
+    journal::{Journal, Outcome, RoundEntry},
+    metrics::{self, METRICS},
+    profitability::{transaction_cost, Profitability},
+    schedule::Schedule,
+    send_and_confirm::{ComputeBudget, Sender},
+    session::Session,
+    shutdown::Shutdown,
+    thermal::Thermal,
+    time_sync::TimeSync,
+    utils::{
+        amount_f64_to_u64, amount_u64_to_string, calculate_reward, get_busses, get_config,
+        get_proof_with_authority, proof_pubkey, stake_multiplier,
+    },
     Miner,
 };
 
+/// The compute units added to a mine transaction by a reset instruction.
+const RESET_COMPUTE_UNITS: u32 = 100_000;
+
+/// The position of the reset instruction in a mine transaction, after the compute budget
+/// and auth instructions.
+const RESET_INSTRUCTION: u8 = 3;
+
+/// The minimum number of seconds to keep mining a challenge that was not submitted.
+const RETRY_WINDOW: u64 = 10;
+
+/// The shortest time the pool spends hashing for one key before moving on, in seconds.
+const MIN_SLICE: u64 = 2;
+
+/// How often to check for finished submissions when there is nothing to hash.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// What to do with a round whose hashing was stopped by the hard deadline. Hashing only
+/// runs into the hard deadline while some thread has not found a hash of the min
+/// difficulty, so the policies differ when other threads have.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum DeadlinePolicy {
+    /// Skip submitting and keep mining the challenge for a better hash.
+    Abandon,
+    /// Submit the best hash if any thread reached the min difficulty, and abandon the
+    /// round otherwise.
+    Submit,
+}
+
+/// The mining state of one authority.
+struct Key {
+    miner: Miner,
+    signer: Keypair,
+    /// Prefix for status lines, empty when mining with a single key.
+    label: String,
+    /// The proof for the current round, or None if a new round must be started.
+    proof: Option<Proof>,
+    deadline: Instant,
+    last_challenge: [u8; 32],
+    last_balance: u64,
+    balance_change: i64,
+    /// The proof's lifetime rewards when last read.
+    last_total_rewards: u64,
+    /// The number of hashing passes over the current challenge.
+    passes: u64,
+    /// Time spent hashing the current challenge.
+    hashed: Duration,
+    best: Option<BestHash>,
+    /// Whether the best hash reached the target or max difficulty, so hashing has stopped.
+    target_reached: bool,
+    /// Whether an unprofitable hash was already kept for this challenge.
+    deferred: bool,
+    simulated_hash_at: Option<i64>,
+    /// When the wallet was last checked for ORE to auto-stake.
+    stake_checked_at: Option<Instant>,
+    /// The submission landing in the background, if any.
+    submission: Option<JoinHandle<Landing>>,
+    /// A bus that ran out paying this key's last reward, to avoid next round.
+    drained_bus: Option<Pubkey>,
+    /// A claim or stake landing in the background, if any. Resolves to the change it made to the
+    /// stake balance, which is not a mining reward.
+    upkeep: Option<JoinHandle<i64>>,
+}
+
+/// A mine transaction to land in the background while the pool hashes for other keys.
+struct Submission {
+    label: String,
+    /// The auth instruction, followed by any reset instruction.
+    ixs: Vec<Instruction>,
+    compute_budget: u32,
+    busses: Vec<Bus>,
+    /// Busses not to submit to.
+    exclude: Vec<Pubkey>,
+    reward: u64,
+    solution: Solution,
+    bus_strategy: BusStrategy,
+    sender: Sender,
+    time: TimeSync,
+    rng: StdRng,
+    entry: RoundEntry,
+}
+
+/// The outcome of a submission.
+struct Landing {
+    entry: RoundEntry,
+    /// The time from sending to confirmation, if the transaction landed.
+    latency: Option<Duration>,
+    /// The proof's lifetime rewards after landing.
+    total_rewards: Option<u64>,
+    /// The reward expected when submitting.
+    expected_reward: u64,
+    /// The bus paid from, if it was left empty after landing.
+    drained_bus: Option<Pubkey>,
+}
+
+/// State shared by every key in a mining session.
+struct Context<'a> {
+    args: &'a MineArgs,
+    pool: HashPool,
+    session: Session,
+    journal: Option<Journal>,
+    time: TimeSync,
+    buffer: Option<AdaptiveBuffer>,
+    /// The stake balance past which to claim, and the wallet to claim to.
+    auto_claim: Option<(u64, Pubkey)>,
+    /// Picks busses and resets for strategies that choose at random.
+    rng: StdRng,
+}
+
+impl Context<'_> {
+    fn buffer_time(&self) -> u64 {
+        self.buffer
+            .as_ref()
+            .map_or(self.args.buffer_time, |buffer| buffer.buffer_time())
+    }
+}
+
+impl Key {
+    fn is_due(&self, now: Instant) -> bool {
+        now.ge(&self.deadline)
+    }
+}
+
 impl Miner {
     pub async fn mine(&self, args: MineArgs) {
-        // Register, if needed.
-        let signer = self.signer();
-        self.open().await;
+        // Finish the current round on SIGINT or SIGTERM
+        let shutdown = Shutdown::listen();
+        let pool = self.hash_pool(&args);
 
-        // Check num threads
-        self.check_num_cores(args.threads);
+        // Mine for a pool server, if given, until it stays unreachable and solo mining
+        // is allowed
+        if let Some(url) = args.pool.as_deref() {
+            if !self.mine_pool(url, &args, &pool, &shutdown).await {
+                return;
+            }
+        }
+
+        // Register each key, if needed. Dry runs never send transactions.
+        let miners = self.key_miners(args.keys_file.as_deref());
+        for miner in miners.iter() {
+            let signer = miner.signer();
+            if !args.dry_run {
+                miner.open().await;
+            } else if self
+                .rpc_client
+                .get_account(&proof_pubkey(signer.pubkey()))
+                .await
+                .is_err()
+            {
+                eprintln!(
+                    "error: No proof account found for {}. Run without --dry-run to open one.",
+                    signer.pubkey()
+                );
+                std::process::exit(1);
+            }
+        }
+
+        if args.min_buffer_time.gt(&args.max_buffer_time) {
+            eprintln!("error: Min buffer time must not exceed max buffer time");
+            std::process::exit(1);
+        }
+
+        // Parse the auto-claim wallet, if given
+        let auto_claim = args
+            .auto_claim_threshold
+            .zip(args.auto_claim_to.as_ref())
+            .map(|(threshold, to)| {
+                let wallet = Pubkey::from_str(to).unwrap_or_else(|_| {
+                    eprintln!("error: Invalid wallet address `{}`", to);
+                    std::process::exit(1);
+                });
+                (amount_f64_to_u64(threshold), wallet)
+            });
+
+        // Mine only within the scheduled windows, if given
+        let schedule = args.schedule.as_ref().map(|windows| {
+            Schedule::new(windows, &args.timezone).unwrap_or_else(|err| {
+                eprintln!("error: {}", err);
+                std::process::exit(1);
+            })
+        });
+
+        let multi_key = miners.len().gt(&1);
+        let mut keys = vec![];
+        for miner in miners {
+            let signer = miner.signer();
+            let label = if multi_key {
+                format!("[{}] ", &signer.pubkey().to_string()[..8])
+            } else {
+                String::new()
+            };
+            let proof = get_proof_with_authority(&self.rpc_client, signer.pubkey()).await;
+            println!(
+                "\n{}Stake balance: {} ORE",
+                label,
+                amount_u64_to_string(proof.balance)
+            );
+            keys.push(Key {
+                miner,
+                signer,
+                label,
+                proof: None,
+                deadline: Instant::now(),
+                last_challenge: [0; 32],
+                last_balance: proof.balance,
+                balance_change: 0,
+                last_total_rewards: proof.total_rewards,
+                passes: 0,
+                hashed: Duration::ZERO,
+                best: None,
+                target_reached: false,
+                deferred: false,
+                simulated_hash_at: None,
+                stake_checked_at: None,
+                submission: None,
+                drained_bus: None,
+                upkeep: None,
+            });
+        }
+
+        // Serve metrics, if requested
+        if let Some(addr) = args.metrics_addr {
+            if let Err(err) = metrics::serve(addr) {
+                eprintln!("error: Could not serve metrics on {}: {}", addr, err);
+                std::process::exit(1);
+            }
+        }
+
+        // Open journal, if requested
+        let journal = args.journal.as_ref().map(|path| {
+            Journal::open(path).unwrap_or_else(|err| {
+                eprintln!("error: Could not open journal `{}`: {}", path, err);
+                std::process::exit(1);
+            })
+        });
+
+        let mut ctx = Context {
+            args: &args,
+            pool,
+            session: Session::new(),
+            journal,
+            time: TimeSync::new(),
+            buffer: args.adaptive_buffer.then(|| {
+                AdaptiveBuffer::new(args.buffer_time, args.min_buffer_time, args.max_buffer_time)
+            }),
+            auto_claim,
+            rng: args
+                .seed
+                .map_or_else(StdRng::from_entropy, StdRng::seed_from_u64),
+        };
+
+        // Start mining loop, sharing the pool between keys in slices
+        let slice = Duration::from_secs((ONE_MINUTE as u64 / keys.len() as u64).max(MIN_SLICE));
+        loop {
+            // After a shutdown request or outside the schedule, only finish rounds that
+            // have started hashing
+            let stopping = shutdown.is_requested();
+            let paused = schedule
+                .as_ref()
+                .is_some_and(|schedule| !schedule.is_open(Utc::now()));
+
+            // Account for submissions that have landed or failed
+            for key in keys.iter_mut() {
+                if !key.submission.as_ref().is_some_and(JoinHandle::is_finished) {
+                    continue;
+                }
+                if let Some(task) = key.submission.take() {
+                    match task.await {
+                        Ok(landing) => Self::record_landing(&mut ctx, key, landing),
+                        Err(err) => std::panic::resume_unwind(err.into_panic()),
+                    }
+                }
+            }
+
+            // Account for claims and stakes that have finished
+            for key in keys.iter_mut() {
+                if !key.upkeep.as_ref().is_some_and(JoinHandle::is_finished) {
+                    continue;
+                }
+                if let Some(task) = key.upkeep.take() {
+                    match task.await {
+                        Ok(change) => {
+                            key.last_balance = key.last_balance.saturating_add_signed(change)
+                        }
+                        Err(err) => std::panic::resume_unwind(err.into_panic()),
+                    }
+                }
+            }
+
+            // A key waits for its claim or stake to settle, so the balance it reads next is not
+            // counted twice
+            if !stopping && !paused {
+                for key in keys.iter_mut().filter(|key| {
+                    key.proof.is_none() && key.submission.is_none() && key.upkeep.is_none()
+                }) {
+                    self.start_round(&mut ctx, key).await;
+                }
+            }
+            let winding_down = stopping || paused;
+            let active = |key: &Key| key.proof.is_some() && (!winding_down || key.best.is_some());
+            let submitting = keys
+                .iter()
+                .any(|key| key.submission.is_some() || key.upkeep.is_some());
+            if !keys.iter().any(active) {
+                // Let submissions in flight finish before stopping or pausing
+                if submitting {
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                    continue;
+                }
+                let Some(schedule) = schedule.as_ref().filter(|_| paused && !stopping) else {
+                    break;
+                };
+
+                // Wait for the next window, starting fresh rounds then
+                for key in keys.iter_mut() {
+                    key.proof = None;
+                }
+                Self::wait_for_window(schedule, &shutdown).await;
+                continue;
+            }
+            METRICS.set_stake_balance(keys.iter().map(|key| key.last_balance).sum());
+            let config = get_config(&self.rpc_client).await;
+
+            // Submit for a key whose deadline has come
+            let now = Instant::now();
+            if let Some(key) = keys.iter_mut().find(|key| active(key) && key.is_due(now)) {
+                self.finish_round(&mut ctx, key, config).await;
+                continue;
+            }
+
+            // Otherwise hash for the key with the least time on its challenge,
+            // stopping in time for the next deadline. Keys that reached the target
+            // difficulty wait for their deadline without hashing, while checking on any
+            // submissions.
+            let next_deadline = keys
+                .iter()
+                .filter(|key| active(key))
+                .map(|key| key.deadline)
+                .min()
+                .unwrap_or(now);
+            let cutoff = next_deadline.min(now + slice);
+            match keys
+                .iter_mut()
+                .filter(|key| active(key) && !key.target_reached)
+                .min_by_key(|key| key.hashed)
+            {
+                Some(key) => Self::hash_key(&ctx, key, cutoff, config).await,
+                None if submitting => {
+                    tokio::time::sleep_until(next_deadline.min(now + POLL_INTERVAL).into()).await
+                }
+                None => tokio::time::sleep_until(next_deadline.into()).await,
+            }
+        }
+
+        // Account for the final round and summarize the session
+        for key in keys.iter() {
+            let proof = get_proof_with_authority(&self.rpc_client, key.signer.pubkey()).await;
+            ctx.session
+                .record_reward(proof.total_rewards.saturating_sub(key.last_total_rewards));
+        }
+        ctx.session.print_summary();
+    }
+
+    /// Starts the hashing threads, with the thread count, placement, and limits requested.
+    fn hash_pool(&self, args: &MineArgs) -> HashPool {
+        // Check num threads, calibrating if requested
+        let partition =
+            NoncePartition::new(args.machine_index, args.machine_count).unwrap_or_else(|err| {
+                eprintln!("error: {}", err);
+                std::process::exit(1);
+            });
+        let placement = args.placement.placement();
+        let threads = match args.threads {
+            Threads::Count(threads) => {
+                self.check_num_cores(threads);
+                threads
+            }
+            Threads::Auto => {
+                tokio::task::block_in_place(|| Self::calibrate_threads(partition, &placement))
+            }
+        };
+        let pool = HashPool::new(threads, partition, placement);
+
+        // Limit CPU use, following the limit file if given
+        pool.throttle().set_cpu_limit(args.cpu_limit);
+        if let Some(path) = args.cpu_limit_file.clone() {
+            pool.throttle().watch_file(path);
+        }
+
+        // Shed threads when running hot, if requested
+        if let Some(high) = args.thermal_high {
+            let low = args.thermal_low.unwrap_or(high - 10.0);
+            let thermal = Thermal::new(&args.thermal_path, high, low).unwrap_or_else(|err| {
+                eprintln!("error: {}", err);
+                std::process::exit(1);
+            });
+            thermal.watch(pool.throttle().clone());
+        }
+        pool
+    }
+
+    /// Returns a miner for each private key in the keys file, or for this miner's key.
+    fn key_miners(&self, keys_file: Option<&str>) -> Vec<Miner> {
+        let Some(path) = keys_file else {
+            return vec![self.with_private_key(self.private_key.clone())];
+        };
+        let contents = std::fs::read_to_string(path).unwrap_or_else(|err| {
+            eprintln!("error: Could not read keys file `{}`: {}", path, err);
+            std::process::exit(1);
+        });
+        let miners: Vec<Miner> = contents
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(i, line)| {
+                let bytes = bs58::decode(line).into_vec().unwrap_or_default();
+                if Keypair::from_bytes(&bytes).is_err() {
+                    eprintln!("error: Invalid private key on line {} of `{}`", i + 1, path);
+                    std::process::exit(1);
+                }
+                self.with_private_key(Some(line.to_string()))
+            })
+            .collect();
+        if miners.is_empty() {
+            eprintln!("error: No private keys found in `{}`", path);
+            std::process::exit(1);
+        }
+        miners
+    }
+
+    /// Fetches a key's proof and sets the deadline for its next submission.
+    async fn start_round(&self, ctx: &mut Context<'_>, key: &mut Key) {
+        // Fetch proof, treating simulated submissions as if they had landed
+        let mut proof = get_proof_with_authority(&self.rpc_client, key.signer.pubkey()).await;
+        if let Some(t) = key.simulated_hash_at {
+            proof.last_hash_at = proof.last_hash_at.max(t);
+        }
+        key.balance_change = proof.balance as i64 - key.last_balance as i64;
+        key.last_balance = proof.balance;
+
+        // Count rewards for hashes that landed without being confirmed
+        ctx.session
+            .record_reward(proof.total_rewards.saturating_sub(key.last_total_rewards));
+        key.last_total_rewards = proof.total_rewards;
+
+        // Claim the stake balance past the threshold in the background, if requested. Dry
+        // runs never send transactions.
+        if let Some((threshold, wallet)) = ctx.auto_claim.filter(|_| !ctx.args.dry_run) {
+            if proof.balance.gt(&threshold) && key.upkeep.is_none() {
+                let miner = key.miner.with_private_key(key.miner.private_key.clone());
+                let label = key.label.clone();
+                let amount = proof.balance;
+                key.upkeep = Some(tokio::spawn(async move {
+                    match miner.claim_to(wallet, amount).await {
+                        Ok(sig) => {
+                            println!(
+                                "{}{} Claimed {} ORE to {} ({})",
+                                label,
+                                "OK".bold().green(),
+                                amount_u64_to_string(amount),
+                                wallet,
+                                sig
+                            );
+                            -(amount as i64)
+                        }
+                        Err(err) => {
+                            println!(
+                                "{}{} Auto-claim failed: {}",
+                                label,
+                                "ERROR".bold().red(),
+                                err
+                            );
+                            0
+                        }
+                    }
+                }));
+            }
+        }
+
+        // Stake wallet ORE past the reserve in the background, if requested
+        if let Some(reserve) = ctx.args.auto_stake_reserve.filter(|_| !ctx.args.dry_run) {
+            let interval = Duration::from_secs(ctx.args.auto_stake_interval);
+            if key.upkeep.is_none()
+                && key
+                    .stake_checked_at
+                    .map_or(true, |t| t.elapsed().ge(&interval))
+            {
+                key.stake_checked_at = Some(Instant::now());
+                let miner = key.miner.with_private_key(key.miner.private_key.clone());
+                let label = key.label.clone();
+                key.upkeep = Some(tokio::spawn(
+                    miner.auto_stake(label, amount_f64_to_u64(reserve)),
+                ));
+            }
+        }
+
+        if proof.challenge.ne(&key.last_challenge) {
+            key.last_challenge = proof.challenge;
+            key.passes = 0;
+            key.hashed = Duration::ZERO;
+            key.best = None;
+            key.deferred = false;
+        }
 
+        // Calc cutoff time against the chain clock
+        ctx.time.sync(&self.rpc_client).await;
+        let buffer_time = ctx.buffer_time();
+        let mut deadline = Self::get_cutoff(proof, buffer_time, &ctx.time);
+        if key.passes.gt(&0) {
+            deadline = deadline.max(Instant::now() + Duration::from_secs(RETRY_WINDOW));
+        }
+
+        // Submit a deferred hash before the liveness penalty starts
+        if key.deferred && key.best.is_some() {
+            let penalty_at = ctx
+                .time
+                .instant_at(proof.last_hash_at.saturating_add(ONE_MINUTE));
+            deadline = deadline.min(penalty_at.max(Instant::now()));
+        }
+        println!(
+            "{}Chain time: {} (skew {:+.2}s ± {:.2}s, RTT {} ms), submitting in {} sec (buffer {} sec)",
+            key.label,
+            ctx.time.unix_timestamp(),
+            ctx.time.skew(),
+            ctx.time.uncertainty(),
+            ctx.time.rtt().as_millis(),
+            deadline.saturating_duration_since(Instant::now()).as_secs(),
+            buffer_time
+        );
+        key.deadline = deadline;
+        key.proof = Some(proof);
+    }
+
+    /// Stakes this key's wallet ORE beyond the reserve, returning the amount staked.
+    async fn auto_stake(self, label: String, reserve: u64) -> i64 {
+        let amount = self
+            .wallet_balance()
+            .await
+            .unwrap_or(0)
+            .saturating_sub(reserve);
+        if amount.eq(&0) {
+            return 0;
+        }
+        if let Err(err) = self.stake_from_wallet(amount).await {
+            println!(
+                "{}{} Auto-stake failed: {}",
+                label,
+                "ERROR".bold().red(),
+                err
+            );
+            return 0;
+        }
+        let signer = self.signer();
         let proof = get_proof_with_authority(&self.rpc_client, signer.pubkey()).await;
+        let config = get_config(&self.rpc_client).await;
         println!(
-            "\nStake balance: {} ORE",
-            amount_u64_to_string(proof.balance)
+            "{}{} Staked {} ORE. Stake balance: {} ORE, multiplier: {:.2}x",
+            label,
+            "OK".bold().green(),
+            amount_u64_to_string(amount),
+            amount_u64_to_string(proof.balance),
+            stake_multiplier(&config, proof.balance)
         );
-        // Start mining loop
-        loop {
-            // Fetch proof
-            let proof = get_proof_with_authority(&self.rpc_client, signer.pubkey()).await;
+        amount as i64
+    }
 
+    /// Hashes a key's challenge for a while, scanning nonces not covered by earlier passes.
+    /// Hashing stops early at the target or max difficulty, neither of which counts below
+    /// the min difficulty the program accepts.
+    async fn hash_key(ctx: &Context<'_>, key: &mut Key, cutoff: Instant, config: Config) {
+        let min_difficulty = config.min_difficulty as u32;
+        let target_difficulty = ctx.args.target_difficulty.map(|t| t.max(min_difficulty));
+        let max_difficulty = ctx.args.max_difficulty.map(|t| t.max(min_difficulty));
+        let Some(proof) = key.proof else {
+            return;
+        };
+        let hard_cutoff = ctx
+            .args
+            .hard_deadline
+            .map(|secs| key.deadline + Duration::from_secs(secs));
+        let timer = Instant::now();
+        let best = Self::find_hash_par(
+            &ctx.pool,
+            &key.label,
+            HashJob {
+                challenge: proof.challenge,
+                nonces: (key.passes << 40)..u64::MAX,
+                cutoff,
+                hard_cutoff,
+                min_difficulty,
+                target_difficulty: match (target_difficulty, max_difficulty) {
+                    (Some(target), Some(max)) => Some(target.min(max)),
+                    (target, max) => target.or(max),
+                },
+            },
+        )
+        .await;
+        key.passes += 1;
+        key.hashed += timer.elapsed();
+        let difficulty = match key.best.as_mut() {
+            Some(prev) => {
+                prev.merge(best);
+                prev.difficulty
+            }
+            None => key.best.insert(best).difficulty,
+        };
+        let reached = |d: Option<u32>| d.is_some_and(|d| difficulty.ge(&d));
+        key.target_reached = reached(target_difficulty) || reached(max_difficulty);
+        if reached(target_difficulty) {
+            // Submit as soon as the program accepts it, rather than at the deadline
+            let spam_window = proof
+                .last_hash_at
+                .saturating_add(ONE_MINUTE)
+                .saturating_sub(TOLERANCE);
+            key.deadline = key.deadline.min(ctx.time.instant_at(spam_window));
+            println!(
+                "{}Target difficulty reached, submitting in {} sec",
+                key.label,
+                key.deadline
+                    .saturating_duration_since(Instant::now())
+                    .as_secs()
+            );
+        } else if key.target_reached {
+            println!(
+                "{}Max difficulty reached, submitting in {} sec",
+                key.label,
+                key.deadline
+                    .saturating_duration_since(Instant::now())
+                    .as_secs()
+            );
+        }
+    }
 
-            // Calc cutoff time
-            let cutoff_time = self.get_cutoff(proof, args.buffer_time).await;
+    /// Submits a key's best hash, landing it in the background.
+    async fn finish_round(&self, ctx: &mut Context<'_>, key: &mut Key, config: Config) {
+        // Make sure there is a hash to submit
+        if key.best.is_none() {
+            Self::hash_key(ctx, key, Instant::now(), config).await;
+        }
+        let args = ctx.args;
+        let (Some(proof), Some(best)) = (key.proof.take(), key.best.take()) else {
+            return;
+        };
+        key.target_reached = false;
+        let miner = &key.miner;
+        let signer = &key.signer;
+        ctx.session.record_round(best.difficulty);
+        let mut entry =
+            RoundEntry::new(signer.pubkey(), proof.challenge, &best, key.balance_change);
 
-            // Run drillx
-            let config = get_config(&self.rpc_client).await;
-            let solution = Self::find_hash_par(
-                proof,
-                cutoff_time,
-                args.threads,
-                config.min_difficulty as u32,
-            )
-            .await;
+        // Handle rounds stopped by the hard deadline. Hashes below the min difficulty are
+        // never submitted.
+        let min_difficulty = config.min_difficulty as u32;
+        if best.hard_stopped {
+            entry.hard_deadline_passed = true;
+            let submit = args.hard_deadline_policy.eq(&DeadlinePolicy::Submit)
+                && best.difficulty.ge(&min_difficulty);
+            println!(
+                "{}{} Hard deadline passed at difficulty {} (min {}), {}",
+                key.label,
+                "WARNING".bold().yellow(),
+                best.difficulty,
+                min_difficulty,
+                if submit {
+                    "submitting it"
+                } else {
+                    "abandoning round"
+                }
+            );
+            if !submit {
+                entry.outcome = Outcome::Abandoned;
+                key.best = Some(best);
+                if let Some(journal) = ctx.journal.as_mut() {
+                    journal.record(&entry);
+                }
+                return;
+            }
+        }
 
-            // Submit most difficult hash
-            let mut compute_budget = 500_000;
-            let mut ixs = vec![ore_api::instruction::auth(proof_pubkey(signer.pubkey()))];
-            if self.should_reset(config).await {
-                compute_budget += 100_000;
-                ixs.push(ore_api::instruction::reset(signer.pubkey()));
+        // Skip hashes the program would reject
+        if let Err(err) = best.verify(&proof.challenge, min_difficulty) {
+            println!(
+                "{}{} Not submitting: {}",
+                key.label,
+                "ERROR".bold().red(),
+                err
+            );
+            entry.error = Some(err);
+            if let Some(journal) = ctx.journal.as_mut() {
+                journal.record(&entry);
+            }
+            return;
+        }
+
+        // Submit most difficult hash, resetting the epoch if the policy calls for it
+        let now = ctx.time.unix_timestamp();
+        let reward = calculate_reward(&config, &proof, best.difficulty, now);
+        let busses = get_busses(&self.rpc_client).await;
+        let mut compute_budget = 500_000;
+        let mut ixs = vec![ore_api::instruction::auth(proof_pubkey(signer.pubkey()))];
+        let due = Self::should_reset(config, &ctx.time);
+        if args
+            .reset_policy
+            .includes_reset(due, &busses, reward, &mut ctx.rng)
+        {
+            compute_budget += RESET_COMPUTE_UNITS;
+            ixs.push(ore_api::instruction::reset(signer.pubkey()));
+            entry.reset = true;
+        }
+        let budget = ComputeBudget::Fixed(compute_budget);
+        let exclude: Vec<Pubkey> = key.drained_bus.take().into_iter().collect();
+
+        // Skip submissions that cost more than they are expected to earn
+        if let Some(ore_price) = args.ore_price {
+            let profitability = Profitability::new(
+                reward,
+                ore_price,
+                self.priority_fee,
+                compute_budget,
+                args.sender,
+            );
+            print!("{}", key.label);
+            profitability.print(args.profit_margin);
+            if !profitability.is_profitable(args.profit_margin) {
+                // Keep the hash and mine on for a better one at most once, and only while
+                // the retry can submit before the liveness penalty cuts the reward
+                let defer = args.defer_unprofitable
+                    && !key.deferred
+                    && now.lt(&proof.last_hash_at.saturating_add(ONE_MINUTE));
+                if defer {
+                    println!("{}Deferring to mine for a better hash", key.label);
+                    entry.outcome = Outcome::Deferred;
+                    key.deferred = true;
+                    key.best = Some(best);
+                } else {
+                    println!("{}Skipping submission", key.label);
+                }
+                if let Some(journal) = ctx.journal.as_mut() {
+                    journal.record(&entry);
+                }
+                return;
             }
+        }
+
+        // Simulate instead of sending, if requested
+        if args.dry_run {
+            let Some(bus) = args
+                .bus_strategy
+                .select(&busses, reward, &exclude, &mut ctx.rng)
+            else {
+                return;
+            };
             ixs.push(ore_api::instruction::mine(
                 signer.pubkey(),
                 signer.pubkey(),
-                find_bus(),
-                solution,
+                bus,
+                best.solution(),
             ));
-            self.jito_send_and_confirm(&ixs, ComputeBudget::Fixed(compute_budget), false)
-                .await
-                .ok();
+            entry.bus = Some(bus.to_string());
+            entry.outcome = Outcome::Simulated;
+            let (tx, _) = match args.sender {
+                Sender::Rpc => miner.build_tx(&ixs, budget).await,
+                Sender::Jito => miner.build_jito_tx(&ixs, budget).await,
+            };
+            match miner.simulate_transaction(&tx).await {
+                Ok(sim) => {
+                    match &sim.err {
+                        Some(err) => {
+                            println!(
+                                "{}{} Simulation failed: {}",
+                                key.label,
+                                "ERROR".bold().red(),
+                                err
+                            );
+                            entry.error = Some(err.to_string());
+                            for log in sim.logs.iter().flatten() {
+                                println!("  {}", log);
+                            }
+                        }
+                        None => {
+                            println!("{}{} Simulation succeeded", key.label, "OK".bold().green())
+                        }
+                    }
+                    println!(
+                        "Compute units consumed: {} of {}, would have cost: {} SOL",
+                        sim.units_consumed.unwrap_or_default(),
+                        compute_budget,
+                        lamports_to_sol(transaction_cost(
+                            self.priority_fee,
+                            compute_budget,
+                            args.sender
+                        ))
+                    );
+                }
+                Err(err) => {
+                    println!("{}{} {}", key.label, "ERROR".bold().red(), err);
+                    entry.error = Some(err.to_string());
+                }
+            }
+            key.simulated_hash_at = Some(now);
+            if let Some(journal) = ctx.journal.as_mut() {
+                journal.record(&entry);
+            }
+            return;
         }
-    }
 
-    async fn find_hash_par(
-        proof: Proof,
-        cutoff_time: u64,
-        threads: u64,
-        min_difficulty: u32,
-    ) -> Solution {
-        // Dispatch job to each thread
-        let progress_bar = Arc::new(spinner::new_progress_bar());
-        progress_bar.set_message("Mining...");
-        let handles: Vec<_> = (0..threads)
-            .map(|i| {
-                std::thread::spawn({
-                    let proof = proof.clone();
-                    let progress_bar = progress_bar.clone();
-                    let mut memory = equix::SolverMemory::new();
-                    move || {
-                        let timer = Instant::now();
-                        let mut nonce = u64::MAX.saturating_div(threads).saturating_mul(i);
-                        let mut best_nonce = nonce;
-                        let mut best_difficulty = 0;
-                        let mut best_hash = Hash::default();
-                        loop {
-                            // Create hash
-                            if let Ok(hx) = drillx::hash_with_memory(
-                                &mut memory,
-                                &proof.challenge,
-                                &nonce.to_le_bytes(),
-                            ) {
-                                let difficulty = hx.difficulty();
-                                if difficulty.gt(&best_difficulty) {
-                                    best_nonce = nonce;
-                                    best_difficulty = difficulty;
-                                    best_hash = hx;
-                                }
-                            }
+        // Land the transaction in the background, so the pool keeps hashing for other keys
+        let submission = Submission {
+            label: key.label.clone(),
+            ixs,
+            compute_budget,
+            busses,
+            exclude,
+            reward,
+            solution: best.solution(),
+            bus_strategy: args.bus_strategy,
+            sender: args.sender,
+            time: ctx.time.clone(),
+            rng: StdRng::from_rng(&mut ctx.rng).expect("Failed to seed submission"),
+            entry,
+        };
+        let miner = miner.with_private_key(miner.private_key.clone());
+        key.submission = Some(tokio::spawn(async move { miner.land(submission).await }));
+    }
 
-                            // Exit if time has elapsed
-                            if nonce % 100 == 0 {
-                                if timer.elapsed().as_secs().ge(&cutoff_time) {
-                                    if best_difficulty.gt(&min_difficulty) {
-                                        // Mine until min difficulty has been met
-                                        break;
-                                    }
-                                } else if i == 0 {
-                                    progress_bar.set_message(format!(
-                                        "Mining... ({} sec remaining)",
-                                        cutoff_time.saturating_sub(timer.elapsed().as_secs()),
-                                    ));
-                                }
-                            }
+    /// Sends a submission's mine transaction, retrying without a failed or redundant reset.
+    async fn land(&self, submission: Submission) -> Landing {
+        let Submission {
+            label,
+            mut ixs,
+            mut compute_budget,
+            busses,
+            exclude,
+            reward,
+            solution,
+            bus_strategy,
+            sender,
+            time,
+            mut rng,
+            mut entry,
+        } = submission;
+        let signer = self.signer();
+        let mut latency = None;
+        let mut drained_bus = None;
+        entry.outcome = Outcome::Failed;
+        let Some(bus) = bus_strategy.select(&busses, reward, &exclude, &mut rng) else {
+            return Landing {
+                entry,
+                latency,
+                total_rewards: None,
+                expected_reward: reward,
+                drained_bus,
+            };
+        };
+        entry.bus = Some(bus.to_string());
+        loop {
+            let mut bus_ixs = ixs.clone();
+            bus_ixs.push(ore_api::instruction::mine(
+                signer.pubkey(),
+                signer.pubkey(),
+                bus,
+                solution,
+            ));
+            let budget = ComputeBudget::Fixed(compute_budget);
 
-                            // Increment nonce
-                            nonce += 1;
-                        }
+            // Drop the reset before sending if another miner has reset since, or it would
+            // fail. Bundles with a failing transaction are dropped without an error, so
+            // Jito submissions are simulated first.
+            if entry.reset && !self.reset_would_land(&bus_ixs, budget, sender, &time).await {
+                println!(
+                    "{}{} Reset is no longer due or would fail, sending without it",
+                    label,
+                    "WARNING".bold().yellow()
+                );
+                ixs.truncate(1);
+                compute_budget -= RESET_COMPUTE_UNITS;
+                entry.reset = false;
+                continue;
+            }
+            let sent_at = Instant::now();
+            match self.submit(&bus_ixs, budget, sender).await {
+                Ok(sig) => {
+                    latency = Some(sent_at.elapsed());
+                    entry.signature = Some(sig.to_string());
+                    entry.outcome = Outcome::Landed;
+                    entry.error = None;
+                    break;
+                }
+                Err(err) => {
+                    println!("{}{} {}", label, "ERROR".bold().red(), err);
+                    entry.error = Some(err.to_string());
 
-                        // Return the best nonce
-                        (best_nonce, best_difficulty, best_hash)
+                    // Retry without the reset if it failed or another miner reset first
+                    let failed_reset = instruction_error(&err)
+                        .is_some_and(|(index, _)| index.eq(&RESET_INSTRUCTION));
+                    if entry.reset
+                        && (failed_reset
+                            || !Self::should_reset(get_config(&self.rpc_client).await, &time))
+                    {
+                        println!(
+                            "{}{} Reset failed or was redundant, retrying without it",
+                            label,
+                            "WARNING".bold().yellow()
+                        );
+                        ixs.truncate(1);
+                        compute_budget -= RESET_COMPUTE_UNITS;
+                        entry.reset = false;
+                        continue;
                     }
-                })
-            })
-            .collect();
-
-        // Join handles and return best nonce
-        let mut best_nonce = 0;
-        let mut best_difficulty = 0;
-        let mut best_hash = Hash::default();
-        for h in handles {
-            if let Ok((nonce, difficulty, hash)) = h.join() {
-                if difficulty > best_difficulty {
-                    best_difficulty = difficulty;
-                    best_nonce = nonce;
-                    best_hash = hash;
+                    break;
                 }
             }
         }
 
-        Solution::new(best_hash.d, best_nonce.to_le_bytes())
+        // Read the proof's lifetime rewards, to account for the realised reward. The
+        // program caps the payout at what the bus has left, so an empty bus may have
+        // paid less than expected.
+        let total_rewards = match entry.outcome {
+            Outcome::Landed => {
+                drained_bus = get_busses(&self.rpc_client)
+                    .await
+                    .iter()
+                    .any(|b| BUS_ADDRESSES[b.id as usize].eq(&bus) && b.rewards.eq(&0))
+                    .then_some(bus);
+                Some(
+                    get_proof_with_authority(&self.rpc_client, signer.pubkey())
+                        .await
+                        .total_rewards,
+                )
+            }
+            _ => None,
+        };
+        Landing {
+            entry,
+            latency,
+            total_rewards,
+            expected_reward: reward,
+            drained_bus,
+        }
+    }
+
+    /// Whether a mine transaction's reset is still due and, for Jito, passes simulation.
+    async fn reset_would_land(
+        &self,
+        ixs: &[Instruction],
+        compute_budget: ComputeBudget,
+        sender: Sender,
+        time: &TimeSync,
+    ) -> bool {
+        if !Self::should_reset(get_config(&self.rpc_client).await, time) {
+            return false;
+        }
+        if sender.ne(&Sender::Jito) {
+            return true;
+        }
+        let (tx, _) = self.build_tx(ixs, compute_budget).await;
+        !self.simulate_transaction(&tx).await.is_ok_and(|sim| {
+            matches!(
+                sim.err,
+                Some(TransactionError::InstructionError(RESET_INSTRUCTION, _))
+            )
+        })
+    }
+
+    /// Accounts for a key's finished submission.
+    fn record_landing(ctx: &mut Context<'_>, key: &mut Key, landing: Landing) {
+        let mut entry = landing.entry;
+        if let Some(buffer) = ctx.buffer.as_mut() {
+            match landing.latency {
+                Some(latency) => buffer.record_landing(latency),
+                None => buffer.record_miss(),
+            }
+        }
+        ctx.session
+            .record_submission(entry.outcome.eq(&Outcome::Landed));
+
+        // Read the realised reward from the proof. Its lifetime rewards only grow with
+        // mining, so stake and claim activity is left out.
+        if let Some(total_rewards) = landing.total_rewards {
+            let reward = total_rewards.saturating_sub(key.last_total_rewards);
+            key.last_total_rewards = total_rewards;
+            ctx.session.record_reward(reward);
+            entry.reward = Some(reward);
+            print!("{}", key.label);
+            ctx.session.print_reward(reward);
+
+            // Pick another bus next round if this one ran out before paying in full
+            if let Some(bus) = landing
+                .drained_bus
+                .filter(|_| reward.lt(&landing.expected_reward))
+            {
+                println!(
+                    "{}{} Bus {} ran out, paying {} of {} ORE. Avoiding it next round.",
+                    key.label,
+                    "WARNING".bold().yellow(),
+                    bus,
+                    amount_u64_to_string(reward),
+                    amount_u64_to_string(landing.expected_reward)
+                );
+                key.drained_bus = Some(bus);
+            }
+        }
+        if let Some(journal) = ctx.journal.as_mut() {
+            journal.record(&entry);
+        }
+    }
+
+    /// Waits until a window of the schedule opens or a shutdown is requested, showing when
+    /// mining resumes.
+    async fn wait_for_window(schedule: &Schedule, shutdown: &Shutdown) {
+        let progress_bar = spinner::new_progress_bar();
+        let next = schedule.next_open(Utc::now());
+        while !schedule.is_open(Utc::now()) && !shutdown.is_requested() {
+            let remaining = (next.to_utc() - Utc::now()).num_seconds().max(0);
+            progress_bar.set_message(format!(
+                "Paused until {} (in {}h {:02}m {:02}s)",
+                next.format("%Y-%m-%d %H:%M %:z"),
+                remaining / 3600,
+                remaining % 3600 / 60,
+                remaining % 60
+            ));
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+        progress_bar.finish_with_message("Resuming mining");
+    }
+
+    async fn submit(
+        &self,
+        ixs: &[Instruction],
+        compute_budget: ComputeBudget,
+        sender: Sender,
+    ) -> eyre::Result<Signature> {
+        match sender {
+            Sender::Rpc => Ok(self.send_and_confirm(ixs, compute_budget, false).await?),
+            Sender::Jito => self.jito_send_and_confirm(ixs, compute_budget, false).await,
+        }
+    }
+
+    async fn find_hash_par(pool: &HashPool, label: &str, job: HashJob) -> BestHash {
+        // Dispatch job to each thread
+        let progress_bar = spinner::new_progress_bar();
+        progress_bar.set_message(format!("{}Mining...", label));
+        let timer = Instant::now();
+        let cutoff = job.cutoff;
+        pool.dispatch(job);
+
+        // Collect results and return best nonce, letting other tasks run meanwhile
+        let best = tokio::task::block_in_place(|| {
+            pool.collect(|| {
+                progress_bar.set_message(format!(
+                    "{}Mining... ({} sec remaining)",
+                    label,
+                    cutoff.saturating_duration_since(Instant::now()).as_secs(),
+                ));
+            })
+        });
+        let secs = timer.elapsed().as_secs_f64();
+        METRICS.record_round(best.total_hashes(), secs, best.difficulty);
+
+        // Report the effective hashrate when throttled
+        let cpu_limit = pool.throttle().cpu_limit();
+        let throttled = if cpu_limit.lt(&100) {
+            format!(
+                ", {:.1} H/sec at {}% CPU",
+                best.total_hashes() as f64 / secs.max(f64::EPSILON),
+                cpu_limit
+            )
+        } else {
+            String::new()
+        };
+        progress_bar.finish_with_message(format!(
+            "{}Best hash: {} (difficulty: {}{})",
+            label,
+            bs58::encode(best.digest).into_string(),
+            best.difficulty,
+            throttled
+        ));
+        best
     }
 
     pub fn check_num_cores(&self, threads: u64) {
         // Check num threads
         let num_cores = num_cpus::get() as u64;
+        let physical_cores = num_cpus::get_physical() as u64;
         if threads.gt(&num_cores) {
             println!(
                 "{} Number of threads ({}) exceeds available cores ({})",
@@ -162,31 +1139,40 @@ impl Miner {
                 threads,
                 num_cores
             );
+        } else if threads.gt(&physical_cores) {
+            println!(
+                "{} Number of threads ({}) exceeds physical cores ({}), so some share a core with an SMT sibling",
+                "WARNING".bold().yellow(),
+                threads,
+                physical_cores
+            );
         }
     }
 
-    async fn should_reset(&self, config: Config) -> bool {
-        let clock = get_clock(&self.rpc_client).await;
+    fn should_reset(config: Config, time: &TimeSync) -> bool {
         config
             .last_reset_at
             .saturating_add(EPOCH_DURATION)
             .saturating_sub(5) // Buffer
-            .le(&clock.unix_timestamp)
+            .le(&time.unix_timestamp())
     }
 
-    async fn get_cutoff(&self, proof: Proof, buffer_time: u64) -> u64 {
-        let clock = get_clock(&self.rpc_client).await;
-        proof
-            .last_hash_at
-            .saturating_add(60)
-            .saturating_sub(buffer_time as i64)
-            .saturating_sub(clock.unix_timestamp)
-            .max(0) as u64
+    /// The local instant to stop hashing and submit, `buffer_time` seconds before the
+    /// proof's next hash is due.
+    fn get_cutoff(proof: Proof, buffer_time: u64, time: &TimeSync) -> Instant {
+        time.instant_at(
+            proof
+                .last_hash_at
+                .saturating_add(ONE_MINUTE)
+                .saturating_sub(buffer_time as i64),
+        )
     }
 }
 
-// TODO Pick a better strategy (avoid draining bus)
-fn find_bus() -> Pubkey {
-    let i = rand::thread_rng().gen_range(0..BUS_COUNT);
-    BUS_ADDRESSES[i]
+/// The instruction that made a transaction fail and its error, if known.
+fn instruction_error(err: &eyre::Report) -> Option<(u8, InstructionError)> {
+    match err.downcast_ref::<ClientError>()?.get_transaction_error()? {
+        TransactionError::InstructionError(index, err) => Some((index, err)),
+        _ => None,
+    }
 }
@@ -0,0 +1,311 @@
+use std::{
+    ops::Range,
+    time::{Duration, Instant},
+};
+
+use colored::*;
+use futures::{
+    stream::{SplitSink, SplitStream},
+    FutureExt, SinkExt, StreamExt,
+};
+use serde::{Deserialize, Serialize};
+use solana_program::pubkey::Pubkey;
+use solana_rpc_client::spinner;
+use solana_sdk::signer::Signer;
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{self, Message},
+    MaybeTlsStream, WebSocketStream,
+};
+
+use crate::{
+    args::MineArgs,
+    hash_pool::{BestHash, HashJob, HashPool},
+    metrics::METRICS,
+    shutdown::Shutdown,
+    Miner,
+};
+
+/// Failed connection attempts in a row before falling back to solo mining, if allowed.
+const MAX_FAILURES: u32 = 5;
+
+/// The longest wait between connection attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// How often the best hash for a job is sent to the pool, if it improved.
+const REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The nonces per thread in a job's first slice, before the hashrate is known. Later
+/// slices are sized to last one report interval.
+const FIRST_SLICE: u64 = 16;
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// A message from the pool server.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage {
+    /// A challenge to hash over a range of nonces, until `cutoff_secs` from now.
+    Job {
+        challenge: String,
+        nonce_start: u64,
+        nonce_end: u64,
+        cutoff_secs: u64,
+        min_difficulty: u32,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// A message to the pool server.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    /// Identifies the miner, so the pool can account for its work.
+    Ready { pubkey: String },
+    /// The best solution found so far for a job.
+    Solution {
+        pubkey: String,
+        challenge: String,
+        digest: String,
+        nonce: u64,
+        difficulty: u32,
+    },
+}
+
+/// Work assigned by the pool.
+struct Job {
+    challenge: [u8; 32],
+    nonces: Range<u64>,
+    cutoff: Instant,
+    min_difficulty: u32,
+}
+
+/// An open connection to a pool server.
+struct Connection {
+    pubkey: Pubkey,
+    sink: SplitSink<Socket, Message>,
+    stream: SplitStream<Socket>,
+}
+
+impl Miner {
+    /// Hashes jobs from a pool server, reconnecting whenever the connection drops. Returns
+    /// whether to fall back to solo mining.
+    pub async fn mine_pool(
+        &self,
+        url: &str,
+        args: &MineArgs,
+        pool: &HashPool,
+        shutdown: &Shutdown,
+    ) -> bool {
+        let pubkey = self.signer().pubkey();
+        let mut failures = 0;
+        while !shutdown.is_requested() {
+            match connect_async(url).await {
+                Ok((socket, _)) => {
+                    println!("{} Connected to pool {}", "OK".bold().green(), url);
+                    failures = 0;
+                    let (sink, stream) = socket.split();
+                    let mut conn = Connection {
+                        pubkey,
+                        sink,
+                        stream,
+                    };
+                    if let Err(err) = conn.serve(pool, shutdown).await {
+                        println!(
+                            "{} Pool connection lost: {}",
+                            "WARNING".bold().yellow(),
+                            err
+                        );
+                    }
+                }
+                Err(err) => {
+                    failures += 1;
+                    println!(
+                        "{} Could not connect to pool {}: {}",
+                        "WARNING".bold().yellow(),
+                        url,
+                        err
+                    );
+                    if args.pool_fallback && failures.ge(&MAX_FAILURES) {
+                        println!(
+                            "{} Pool unreachable, falling back to solo mining",
+                            "WARNING".bold().yellow()
+                        );
+                        return true;
+                    }
+                }
+            }
+
+            // Back off before reconnecting
+            if !shutdown.is_requested() {
+                let backoff = Duration::from_secs(1 << failures.min(5)).min(MAX_BACKOFF);
+                tokio::time::sleep(backoff).await;
+            }
+        }
+        false
+    }
+}
+
+impl Connection {
+    /// Hashes jobs as they arrive until shutdown, switching to a new job as soon as the
+    /// pool sends one.
+    async fn serve(&mut self, pool: &HashPool, shutdown: &Shutdown) -> Result<(), String> {
+        self.send(&ClientMessage::Ready {
+            pubkey: self.pubkey.to_string(),
+        })
+        .await?;
+        let mut job = None;
+        while !shutdown.is_requested() {
+            match job.take() {
+                Some(current) => job = self.hash(current, pool, shutdown).await?,
+                None => {
+                    let next = tokio::time::timeout(Duration::from_secs(1), self.stream.next());
+                    if let Ok(msg) = next.await {
+                        job = read_job(msg)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Hashes a job slice by slice, sending each improvement on the best hash. Returns the
+    /// next job, if one arrived in the meantime. Stops between slices on shutdown.
+    async fn hash(
+        &mut self,
+        job: Job,
+        pool: &HashPool,
+        shutdown: &Shutdown,
+    ) -> Result<Option<Job>, String> {
+        let progress_bar = spinner::new_progress_bar();
+        let challenge = bs58::encode(job.challenge).into_string();
+        let timer = Instant::now();
+        let mut best = BestHash::default();
+        let mut sent = 0;
+        let mut span = pool.threads().saturating_mul(FIRST_SLICE);
+        let mut start = job.nonces.start;
+        while start.lt(&job.nonces.end)
+            && Instant::now().lt(&job.cutoff)
+            && !shutdown.is_requested()
+        {
+            // Hash the next slice until the next report
+            let end = start.saturating_add(span).min(job.nonces.end);
+            let slice_timer = Instant::now();
+            pool.dispatch(HashJob {
+                challenge: job.challenge,
+                nonces: start..end,
+                cutoff: (Instant::now() + REPORT_INTERVAL).min(job.cutoff),
+                hard_cutoff: Some(job.cutoff),
+                min_difficulty: 0,
+                target_difficulty: None,
+            });
+            let hash = tokio::task::block_in_place(|| {
+                pool.collect(|| {
+                    progress_bar.set_message(format!(
+                        "Mining for pool... (difficulty {}, {} sec remaining)",
+                        best.difficulty,
+                        job.cutoff
+                            .saturating_duration_since(Instant::now())
+                            .as_secs(),
+                    ));
+                })
+            });
+            start = end;
+
+            // Size the next slice to last one report interval at the measured hashrate
+            let rate = hash.total_hashes() as f64 / slice_timer.elapsed().as_secs_f64();
+            span = ((rate * REPORT_INTERVAL.as_secs_f64()) as u64).max(pool.threads());
+            best.merge(hash);
+
+            // Send the best hash if it improved and the pool will accept it
+            if best.difficulty.gt(&sent) && best.verify(&job.challenge, job.min_difficulty).is_ok()
+            {
+                self.send(&ClientMessage::Solution {
+                    pubkey: self.pubkey.to_string(),
+                    challenge: challenge.clone(),
+                    digest: bs58::encode(best.digest).into_string(),
+                    nonce: best.nonce,
+                    difficulty: best.difficulty,
+                })
+                .await?;
+                sent = best.difficulty;
+            }
+
+            // Switch to a new job, if the pool sent one
+            while let Some(msg) = self.stream.next().now_or_never() {
+                if let Some(next) = read_job(msg)? {
+                    progress_bar.finish_with_message(format!(
+                        "New job received. Best hash sent: difficulty {}",
+                        sent
+                    ));
+                    return Ok(Some(next));
+                }
+            }
+        }
+        METRICS.record_round(
+            best.total_hashes(),
+            timer.elapsed().as_secs_f64(),
+            best.difficulty,
+        );
+        progress_bar.finish_with_message(format!(
+            "Best hash: {} (difficulty: {}, sent: {})",
+            bs58::encode(best.digest).into_string(),
+            best.difficulty,
+            sent
+        ));
+        Ok(None)
+    }
+
+    async fn send(&mut self, msg: &ClientMessage) -> Result<(), String> {
+        let text = serde_json::to_string(msg).map_err(|err| err.to_string())?;
+        self.sink
+            .send(Message::Text(text))
+            .await
+            .map_err(|err| err.to_string())
+    }
+}
+
+/// Reads a message from the pool, returning the job it assigns, if any.
+fn read_job(msg: Option<Result<Message, tungstenite::Error>>) -> Result<Option<Job>, String> {
+    let text = match msg {
+        None | Some(Ok(Message::Close(_))) => return Err("Closed by server".to_string()),
+        Some(Err(err)) => return Err(err.to_string()),
+        Some(Ok(Message::Text(text))) => text,
+        Some(Ok(_)) => return Ok(None),
+    };
+    match serde_json::from_str::<ServerMessage>(&text) {
+        Ok(ServerMessage::Job {
+            challenge,
+            nonce_start,
+            nonce_end,
+            cutoff_secs,
+            min_difficulty,
+        }) => {
+            let challenge: [u8; 32] = bs58::decode(&challenge)
+                .into_vec()
+                .ok()
+                .and_then(|bytes| bytes.try_into().ok())
+                .ok_or_else(|| format!("Invalid challenge `{}`", challenge))?;
+            let cutoff = Instant::now()
+                .checked_add(Duration::from_secs(cutoff_secs))
+                .ok_or_else(|| format!("Invalid cutoff `{}` seconds", cutoff_secs))?;
+            Ok(Some(Job {
+                challenge,
+                nonces: nonce_start..nonce_end,
+                cutoff,
+                min_difficulty,
+            }))
+        }
+        Ok(ServerMessage::Other) => Ok(None),
+        Err(err) => {
+            println!(
+                "{} Ignoring pool message: {}",
+                "WARNING".bold().yellow(),
+                err
+            );
+            Ok(None)
+        }
+    }
+}
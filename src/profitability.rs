@@ -0,0 +1,79 @@
+use colored::*;
+use solana_program::native_token::lamports_to_sol;
+
+use crate::{jito_send_and_confirm::JITO_TIP, send_and_confirm::Sender, utils::amount_u64_to_f64};
+
+/// The base fee paid per transaction signature, in lamports.
+pub const SIGNATURE_FEE: u64 = 5000;
+
+/// The expected value of a submission weighed against what it costs to send.
+pub struct Profitability {
+    /// The expected reward, in grains.
+    pub reward: u64,
+    /// The expected reward, valued in SOL.
+    pub reward_sol: f64,
+    /// The total cost of the transaction, in lamports.
+    pub cost: u64,
+}
+
+impl Profitability {
+    pub fn new(
+        reward: u64,
+        ore_price: f64,
+        priority_fee: u64,
+        compute_budget: u32,
+        sender: Sender,
+    ) -> Self {
+        Self {
+            reward,
+            reward_sol: amount_u64_to_f64(reward) * ore_price,
+            cost: transaction_cost(priority_fee, compute_budget, sender),
+        }
+    }
+
+    /// The expected profit over cost, as a percentage of cost.
+    pub fn margin(&self) -> f64 {
+        let cost_sol = lamports_to_sol(self.cost);
+        if cost_sol.eq(&0.0) {
+            return f64::INFINITY;
+        }
+        (self.reward_sol - cost_sol) / cost_sol * 100.0
+    }
+
+    pub fn is_profitable(&self, min_margin: f64) -> bool {
+        self.margin().ge(&min_margin)
+    }
+
+    pub fn print(&self, min_margin: f64) {
+        println!(
+            "Expected reward: {} ORE ({:.6} SOL), cost: {:.6} SOL, margin: {:.1}% {}",
+            amount_u64_to_f64(self.reward),
+            self.reward_sol,
+            lamports_to_sol(self.cost),
+            self.margin(),
+            if self.is_profitable(min_margin) {
+                "SUBMIT".bold().green()
+            } else {
+                "SKIP".bold().yellow()
+            }
+        );
+    }
+}
+
+/// The total cost of a mine transaction in lamports: signature fee, priority fee, and any tip.
+pub fn transaction_cost(priority_fee: u64, compute_budget: u32, sender: Sender) -> u64 {
+    let tip = match sender {
+        Sender::Rpc => 0,
+        Sender::Jito => JITO_TIP,
+    };
+    SIGNATURE_FEE
+        .saturating_add(priority_fee_cost(priority_fee, compute_budget))
+        .saturating_add(tip)
+}
+
+/// The priority fee paid for a compute budget, in lamports.
+pub fn priority_fee_cost(priority_fee: u64, compute_budget: u32) -> u64 {
+    (priority_fee as u128)
+        .saturating_mul(compute_budget as u128)
+        .saturating_div(1_000_000) as u64
+}
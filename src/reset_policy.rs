@@ -0,0 +1,124 @@
+use std::str::FromStr;
+
+use ore_api::state::Bus;
+use rand::Rng;
+
+/// The chance of resetting under the probabilistic policy, when not given.
+const DEFAULT_PROBABILITY: f64 = 0.5;
+
+/// When the miner adds a reset instruction to a mine transaction that falls at the end of
+/// an epoch. Every miner races to reset, and only the first reset does anything.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResetPolicy {
+    /// Whenever the epoch is ending.
+    AlwaysWhenDue,
+    /// Never, leaving resets to other miners.
+    Never,
+    /// When the epoch is ending and no bus can cover the reward.
+    OnlyIfBussesEmpty,
+    /// When the epoch is ending, with the given probability.
+    Probabilistic(f64),
+}
+
+impl FromStr for ResetPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "always-when-due" => Ok(Self::AlwaysWhenDue),
+            "never" => Ok(Self::Never),
+            "only-if-busses-empty" => Ok(Self::OnlyIfBussesEmpty),
+            "probabilistic" => Ok(Self::Probabilistic(DEFAULT_PROBABILITY)),
+            _ => match s.strip_prefix("probabilistic:").map(str::parse::<f64>) {
+                Some(Ok(p)) if (0.0..=1.0).contains(&p) => Ok(Self::Probabilistic(p)),
+                _ => Err(
+                    "expected always-when-due, never, only-if-busses-empty, probabilistic, \
+                     or probabilistic:P with P from 0 to 1"
+                        .to_string(),
+                ),
+            },
+        }
+    }
+}
+
+impl ResetPolicy {
+    /// Whether to reset, given whether the epoch is ending and the reward to be paid out.
+    pub fn includes_reset(
+        &self,
+        due: bool,
+        busses: &[Bus],
+        reward: u64,
+        rng: &mut impl Rng,
+    ) -> bool {
+        if !due {
+            return false;
+        }
+        match self {
+            Self::AlwaysWhenDue => true,
+            Self::Never => false,
+            Self::OnlyIfBussesEmpty => busses.iter().all(|bus| bus.rewards.lt(&reward)),
+            Self::Probabilistic(p) => rng.gen_bool(*p),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn parses() {
+        assert_eq!("always-when-due".parse(), Ok(ResetPolicy::AlwaysWhenDue));
+        assert_eq!("never".parse(), Ok(ResetPolicy::Never));
+        assert_eq!(
+            "only-if-busses-empty".parse(),
+            Ok(ResetPolicy::OnlyIfBussesEmpty)
+        );
+        assert_eq!(
+            "probabilistic".parse(),
+            Ok(ResetPolicy::Probabilistic(DEFAULT_PROBABILITY))
+        );
+        assert_eq!(
+            "probabilistic:0.25".parse(),
+            Ok(ResetPolicy::Probabilistic(0.25))
+        );
+        assert_eq!(
+            "probabilistic:1".parse(),
+            Ok(ResetPolicy::Probabilistic(1.0))
+        );
+        for invalid in [
+            "",
+            "always",
+            "probabilistic:",
+            "probabilistic:1.5",
+            "probabilistic:-0.1",
+            "probabilistic:NaN",
+        ] {
+            assert!(invalid.parse::<ResetPolicy>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn resets_only_when_due() {
+        let busses = [Bus {
+            id: 0,
+            rewards: 5,
+            theoretical_rewards: 0,
+            top_balance: 0,
+        }];
+        let mut rng = StdRng::seed_from_u64(0);
+        for policy in [
+            ResetPolicy::AlwaysWhenDue,
+            ResetPolicy::OnlyIfBussesEmpty,
+            ResetPolicy::Probabilistic(1.0),
+        ] {
+            assert!(!policy.includes_reset(false, &busses, 10, &mut rng));
+            assert!(policy.includes_reset(true, &busses, 10, &mut rng));
+        }
+        assert!(!ResetPolicy::Never.includes_reset(true, &busses, 10, &mut rng));
+        assert!(!ResetPolicy::OnlyIfBussesEmpty.includes_reset(true, &busses, 5, &mut rng));
+        assert!(!ResetPolicy::Probabilistic(0.0).includes_reset(true, &busses, 10, &mut rng));
+    }
+}
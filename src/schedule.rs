@@ -0,0 +1,151 @@
+use chrono::{DateTime, Duration, FixedOffset, NaiveTime, Utc};
+
+use crate::timezone::Zone;
+
+/// A daily range of local time, from `start` up to `end`. Ranges that end before they
+/// start run past midnight, and ranges that end where they start cover the whole day.
+#[derive(Clone, Copy, Debug)]
+struct Window {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl Window {
+    fn contains(&self, time: NaiveTime) -> bool {
+        if self.start.lt(&self.end) {
+            time.ge(&self.start) && time.lt(&self.end)
+        } else {
+            time.ge(&self.start) || time.lt(&self.end)
+        }
+    }
+}
+
+/// The daily windows in which the miner may run, in a timezone.
+#[derive(Clone, Debug)]
+pub struct Schedule {
+    windows: Vec<Window>,
+    zone: Zone,
+}
+
+impl Schedule {
+    /// Parses comma-separated `HH:MM-HH:MM` windows and a timezone, either a UTC offset
+    /// like `+02:00` or a zone like `Europe/Berlin` whose windows follow daylight saving.
+    pub fn new(windows: &str, zone: &str) -> Result<Self, String> {
+        let zone = zone.parse::<Zone>()?;
+        let windows = windows
+            .split(',')
+            .map(|window| {
+                let invalid = || format!("Invalid window `{}`, expected e.g. 22:00-06:00", window);
+                let (start, end) = window.trim().split_once('-').ok_or_else(invalid)?;
+                let parse = |s: &str| NaiveTime::parse_from_str(s.trim(), "%H:%M");
+                Ok(Window {
+                    start: parse(start).map_err(|_| invalid())?,
+                    end: parse(end).map_err(|_| invalid())?,
+                })
+            })
+            .collect::<Result<Vec<Window>, String>>()?;
+        Ok(Self { windows, zone })
+    }
+
+    pub fn is_open(&self, now: DateTime<Utc>) -> bool {
+        let time = self.local(now).time();
+        self.windows.iter().any(|window| window.contains(time))
+    }
+
+    /// The local time the next window opens, or now if one is open.
+    pub fn next_open(&self, now: DateTime<Utc>) -> DateTime<FixedOffset> {
+        if self.is_open(now) {
+            return self.local(now);
+        }
+
+        // Windows open on the minute, so step through the minutes of the next two days.
+        // Stepping in UTC copes with days that daylight saving makes longer or shorter.
+        let minute = DateTime::from_timestamp(now.timestamp() / 60 * 60, 0).unwrap_or(now);
+        (1..=2 * 24 * 60)
+            .map(|i| minute + Duration::minutes(i))
+            .find(|time| self.is_open(*time))
+            .map_or_else(|| self.local(now), |time| self.local(time))
+    }
+
+    fn local(&self, time: DateTime<Utc>) -> DateTime<FixedOffset> {
+        time.with_timezone(&self.zone.offset_at(time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn utc(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 1, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn wraps_past_midnight() {
+        let schedule = Schedule::new("22:00-06:00", "+00:00").unwrap();
+        assert!(schedule.is_open(utc(23, 30)));
+        assert!(schedule.is_open(utc(0, 0)));
+        assert!(schedule.is_open(utc(5, 59)));
+        assert!(!schedule.is_open(utc(6, 0)));
+        assert!(!schedule.is_open(utc(21, 59)));
+        assert!(schedule.is_open(utc(22, 0)));
+    }
+
+    #[test]
+    fn applies_offset() {
+        // 22:00-06:00 at +02:00 is 20:00-04:00 UTC
+        let schedule = Schedule::new("22:00-06:00", "+02:00").unwrap();
+        assert!(schedule.is_open(utc(20, 0)));
+        assert!(schedule.is_open(utc(3, 59)));
+        assert!(!schedule.is_open(utc(4, 0)));
+        assert!(!schedule.is_open(utc(19, 59)));
+    }
+
+    #[test]
+    fn follows_daylight_saving() {
+        // 22:00-06:00 in Central Europe is 21:00-05:00 UTC in winter and 20:00-04:00 in
+        // summer
+        let schedule = Schedule::new("22:00-06:00", "CET-1CEST,M3.5.0,M10.5.0/3").unwrap();
+        let at = |m, d, h| Utc.with_ymd_and_hms(2024, m, d, h, 0, 0).unwrap();
+        assert!(!schedule.is_open(at(1, 15, 20)));
+        assert!(schedule.is_open(at(1, 15, 21)));
+        assert!(schedule.is_open(at(7, 15, 20)));
+        assert!(!schedule.is_open(at(7, 15, 4)));
+
+        // The window after the clocks go forward opens an hour earlier in UTC
+        assert_eq!(schedule.next_open(at(3, 30, 12)), at(3, 30, 21));
+        assert_eq!(schedule.next_open(at(3, 31, 12)), at(3, 31, 20));
+    }
+
+    #[test]
+    fn finds_next_open() {
+        let schedule = Schedule::new("22:00-06:00,12:00-14:00", "+00:00").unwrap();
+        assert_eq!(schedule.next_open(utc(7, 0)), utc(12, 0));
+        assert_eq!(schedule.next_open(utc(15, 0)), utc(22, 0));
+        assert_eq!(schedule.next_open(utc(23, 0)), utc(23, 0));
+
+        // A window that already opened today next opens tomorrow
+        let schedule = Schedule::new("02:00-03:00", "+00:00").unwrap();
+        assert_eq!(
+            schedule.next_open(utc(4, 0)),
+            Utc.with_ymd_and_hms(2024, 3, 2, 2, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn covers_whole_day() {
+        let schedule = Schedule::new("08:00-08:00", "+00:00").unwrap();
+        assert!(schedule.is_open(utc(7, 59)));
+        assert!(schedule.is_open(utc(8, 0)));
+        assert!(schedule.is_open(utc(20, 0)));
+    }
+
+    #[test]
+    fn rejects_invalid() {
+        assert!(Schedule::new("22:00", "+00:00").is_err());
+        assert!(Schedule::new("25:00-06:00", "+00:00").is_err());
+        assert!(Schedule::new("22:00-06:00", "Nowhere/Invalid").is_err());
+    }
+}
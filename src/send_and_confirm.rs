@@ -1,9 +1,11 @@
 use std::time::Duration;
 
+use clap::ValueEnum;
 use colored::*;
 use solana_client::{
     client_error::{ClientError, ClientErrorKind, Result as ClientResult},
-    rpc_config::RpcSendTransactionConfig,
+    rpc_config::{RpcSendTransactionConfig, RpcSimulateTransactionConfig},
+    rpc_response::RpcSimulateTransactionResult,
 };
 use solana_program::{
     instruction::Instruction,
@@ -18,23 +20,43 @@ use solana_sdk::{
 };
 use solana_transaction_status::{TransactionConfirmationStatus, UiTransactionEncoding};
 
-use crate::Miner;
+use crate::{metrics::METRICS, profitability::priority_fee_cost, Miner};
 
 pub(crate) const MIN_SOL_BALANCE: f64 = 0.005;
 
 const RPC_RETRIES: usize = 0;
-const _SIMULATION_RETRIES: usize = 4;
 const GATEWAY_RETRIES: usize = 150;
 const CONFIRM_RETRIES: usize = 1;
 
 const CONFIRM_DELAY: u64 = 0;
 const GATEWAY_DELAY: u64 = 300;
 
+#[derive(Clone, Copy)]
 pub enum ComputeBudget {
     Dynamic,
     Fixed(u32),
 }
 
+/// The path through which transactions are submitted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Sender {
+    /// Send directly to the RPC provider.
+    Rpc,
+    /// Send as a bundle to the Jito block engine, with a tip.
+    Jito,
+}
+
+impl Sender {
+    pub const ALL: [Sender; 2] = [Sender::Rpc, Sender::Jito];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Sender::Rpc => "rpc",
+            Sender::Jito => "jito",
+        }
+    }
+}
+
 impl Miner {
     pub async fn send_and_confirm(
         &self,
@@ -48,6 +70,7 @@ impl Miner {
 
         // Return error, if balance is zero
         if let Ok(balance) = client.get_balance(&signer.pubkey()).await {
+            METRICS.set_sol_balance(balance);
             if balance <= sol_to_lamports(MIN_SOL_BALANCE) {
                 panic!(
                     "{} Insufficient balance: {} SOL\nPlease top up with at least {} SOL",
@@ -58,23 +81,7 @@ impl Miner {
             }
         }
 
-        // Set compute units
-        let mut final_ixs = vec![];
-        match compute_budget {
-            ComputeBudget::Dynamic => {
-                // TODO simulate
-                final_ixs.push(ComputeBudgetInstruction::set_compute_unit_limit(1_400_000))
-            }
-            ComputeBudget::Fixed(cus) => {
-                final_ixs.push(ComputeBudgetInstruction::set_compute_unit_limit(cus))
-            }
-        }
-        final_ixs.push(ComputeBudgetInstruction::set_compute_unit_price(
-            self.priority_fee,
-        ));
-        final_ixs.extend_from_slice(ixs);
-
-        // Build tx
+        // Build and sign tx
         let send_cfg = RpcSendTransactionConfig {
             skip_preflight: true,
             preflight_commitment: Some(CommitmentLevel::Confirmed),
@@ -82,20 +89,19 @@ impl Miner {
             max_retries: Some(RPC_RETRIES),
             min_context_slot: None,
         };
-        let mut tx = Transaction::new_with_payer(&final_ixs, Some(&signer.pubkey()));
-
-        // Sign tx
-        let (hash, _slot) = client
-            .get_latest_blockhash_with_commitment(self.rpc_client.commitment())
-            .await
-            .unwrap();
-        tx.sign(&[&signer], hash);
+        let (tx, cus) = self.build_tx(ixs, compute_budget).await;
 
         // Submit tx
         let mut attempts = 0;
+        let mut sent = false;
         loop {
             match client.send_transaction_with_config(&tx, send_cfg).await {
                 Ok(sig) => {
+                    if !sent {
+                        METRICS.record_sent(Sender::Rpc);
+                        sent = true;
+                    }
+
                     // Skip confirmation
                     if skip_confirm {
                         progress_bar.finish_with_message(format!("Sent: {}", sig));
@@ -104,34 +110,42 @@ impl Miner {
 
                     // Confirm the tx landed
                     for _ in 0..CONFIRM_RETRIES {
-                        std::thread::sleep(Duration::from_millis(CONFIRM_DELAY));
+                        tokio::time::sleep(Duration::from_millis(CONFIRM_DELAY)).await;
                         match client.get_signature_statuses(&[sig]).await {
                             Ok(signature_statuses) => {
-                                for status in signature_statuses.value {
-                                    if let Some(status) = status {
-                                        if let Some(err) = status.err {
-                                            progress_bar.finish_with_message(format!(
-                                                "{}: {}",
-                                                "ERROR".bold().red(),
-                                                err
-                                            ));
-                                            return Err(ClientError {
-                                                request: None,
-                                                kind: ClientErrorKind::Custom(err.to_string()),
-                                            });
-                                        }
-                                        if let Some(confirmation) = status.confirmation_status {
-                                            match confirmation {
-                                                TransactionConfirmationStatus::Processed => {}
-                                                TransactionConfirmationStatus::Confirmed
-                                                | TransactionConfirmationStatus::Finalized => {
-                                                    progress_bar.finish_with_message(format!(
-                                                        "{} {}",
-                                                        "OK".bold().green(),
-                                                        sig
-                                                    ));
-                                                    return Ok(sig);
-                                                }
+                                for status in signature_statuses.value.into_iter().flatten() {
+                                    if let Some(err) = status.err {
+                                        METRICS.record_failed(Sender::Rpc);
+                                        METRICS.record_spend(
+                                            priority_fee_cost(self.priority_fee, cus),
+                                            0,
+                                        );
+                                        progress_bar.finish_with_message(format!(
+                                            "{}: {}",
+                                            "ERROR".bold().red(),
+                                            err
+                                        ));
+                                        return Err(ClientError {
+                                            request: None,
+                                            kind: ClientErrorKind::TransactionError(err),
+                                        });
+                                    }
+                                    if let Some(confirmation) = status.confirmation_status {
+                                        match confirmation {
+                                            TransactionConfirmationStatus::Processed => {}
+                                            TransactionConfirmationStatus::Confirmed
+                                            | TransactionConfirmationStatus::Finalized => {
+                                                METRICS.record_landed(Sender::Rpc);
+                                                METRICS.record_spend(
+                                                    priority_fee_cost(self.priority_fee, cus),
+                                                    0,
+                                                );
+                                                progress_bar.finish_with_message(format!(
+                                                    "{} {}",
+                                                    "OK".bold().green(),
+                                                    sig
+                                                ));
+                                                return Ok(sig);
                                             }
                                         }
                                     }
@@ -143,7 +157,7 @@ impl Miner {
                                 progress_bar.set_message(format!(
                                     "{}: {}",
                                     "ERROR".bold().red(),
-                                    err.kind().to_string()
+                                    err.kind()
                                 ));
                             }
                         }
@@ -152,18 +166,15 @@ impl Miner {
 
                 // Handle submit errors
                 Err(err) => {
-                    progress_bar.set_message(format!(
-                        "{}: {}",
-                        "ERROR".bold().red(),
-                        err.kind().to_string()
-                    ));
+                    progress_bar.set_message(format!("{}: {}", "ERROR".bold().red(), err.kind()));
                 }
             }
 
             // Retry
-            std::thread::sleep(Duration::from_millis(GATEWAY_DELAY));
+            tokio::time::sleep(Duration::from_millis(GATEWAY_DELAY)).await;
             attempts += 1;
             if attempts > GATEWAY_RETRIES {
+                METRICS.record_failed(Sender::Rpc);
                 progress_bar.finish_with_message(format!("{}: Max retries", "ERROR".bold().red()));
                 return Err(ClientError {
                     request: None,
@@ -173,60 +184,60 @@ impl Miner {
         }
     }
 
-    // TODO
-    fn _simulate(&self) {
-
-        // Simulate tx
-        // let mut sim_attempts = 0;
-        // 'simulate: loop {
-        //     let sim_res = client
-        //         .simulate_transaction_with_config(
-        //             &tx,
-        //             RpcSimulateTransactionConfig {
-        //                 sig_verify: false,
-        //                 replace_recent_blockhash: true,
-        //                 commitment: Some(self.rpc_client.commitment()),
-        //                 encoding: Some(UiTransactionEncoding::Base64),
-        //                 accounts: None,
-        //                 min_context_slot: Some(slot),
-        //                 inner_instructions: false,
-        //             },
-        //         )
-        //         .await;
-        //     match sim_res {
-        //         Ok(sim_res) => {
-        //             if let Some(err) = sim_res.value.err {
-        //                 println!("Simulaton error: {:?}", err);
-        //                 sim_attempts += 1;
-        //             } else if let Some(units_consumed) = sim_res.value.units_consumed {
-        //                 if dynamic_cus {
-        //                     println!("Dynamic CUs: {:?}", units_consumed);
-        //                     let cu_budget_ix = ComputeBudgetInstruction::set_compute_unit_limit(
-        //                         units_consumed as u32 + 1000,
-        //                     );
-        //                     let cu_price_ix =
-        //                         ComputeBudgetInstruction::set_compute_unit_price(self.priority_fee);
-        //                     let mut final_ixs = vec![];
-        //                     final_ixs.extend_from_slice(&[cu_budget_ix, cu_price_ix]);
-        //                     final_ixs.extend_from_slice(ixs);
-        //                     tx = Transaction::new_with_payer(&final_ixs, Some(&signer.pubkey()));
-        //                 }
-        //                 break 'simulate;
-        //             }
-        //         }
-        //         Err(err) => {
-        //             println!("Simulaton error: {:?}", err);
-        //             sim_attempts += 1;
-        //         }
-        //     }
-
-        //     // Abort if sim fails
-        //     if sim_attempts.gt(&SIMULATION_RETRIES) {
-        //         return Err(ClientError {
-        //             request: None,
-        //             kind: ClientErrorKind::Custom("Simulation failed".into()),
-        //         });
-        //     }
-        // }
+    /// Builds a signed transaction with compute budget instructions.
+    /// Returns the transaction and its compute unit limit.
+    pub async fn build_tx(
+        &self,
+        ixs: &[Instruction],
+        compute_budget: ComputeBudget,
+    ) -> (Transaction, u32) {
+        let signer = self.signer();
+
+        // Set compute units
+        let mut final_ixs = vec![];
+        let cus = match compute_budget {
+            // TODO simulate
+            ComputeBudget::Dynamic => 1_400_000,
+            ComputeBudget::Fixed(cus) => cus,
+        };
+        final_ixs.push(ComputeBudgetInstruction::set_compute_unit_limit(cus));
+        final_ixs.push(ComputeBudgetInstruction::set_compute_unit_price(
+            self.priority_fee,
+        ));
+        final_ixs.extend_from_slice(ixs);
+
+        // Build tx
+        let mut tx = Transaction::new_with_payer(&final_ixs, Some(&signer.pubkey()));
+
+        // Sign tx
+        let (hash, _slot) = self
+            .rpc_client
+            .get_latest_blockhash_with_commitment(self.rpc_client.commitment())
+            .await
+            .unwrap();
+        tx.sign(&[&signer], hash);
+        (tx, cus)
+    }
+
+    /// Simulates a signed transaction against the latest bank state.
+    pub async fn simulate_transaction(
+        &self,
+        tx: &Transaction,
+    ) -> ClientResult<RpcSimulateTransactionResult> {
+        self.rpc_client
+            .simulate_transaction_with_config(
+                tx,
+                RpcSimulateTransactionConfig {
+                    sig_verify: false,
+                    replace_recent_blockhash: true,
+                    commitment: Some(self.rpc_client.commitment()),
+                    encoding: Some(UiTransactionEncoding::Base64),
+                    accounts: None,
+                    min_context_slot: None,
+                    inner_instructions: false,
+                },
+            )
+            .await
+            .map(|res| res.value)
     }
 }
@@ -0,0 +1,126 @@
+use std::time::Instant;
+
+use colored::*;
+use solana_program::native_token::lamports_to_sol;
+
+use crate::{
+    metrics::METRICS,
+    utils::{amount_u64_to_f64, amount_u64_to_string},
+};
+
+/// Running totals for a single `ore mine` invocation.
+pub struct Session {
+    started: Instant,
+    rounds: u64,
+    submitted: u64,
+    landed: u64,
+    difficulty_total: u64,
+    /// Rewards paid out for landed hashes.
+    earned: u64,
+    spent_at_start: u64,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            rounds: 0,
+            submitted: 0,
+            landed: 0,
+            difficulty_total: 0,
+            earned: 0,
+            spent_at_start: METRICS.spend(),
+        }
+    }
+
+    pub fn record_round(&mut self, difficulty: u32) {
+        self.rounds += 1;
+        self.difficulty_total += difficulty as u64;
+    }
+
+    pub fn record_submission(&mut self, landed: bool) {
+        self.submitted += 1;
+        if landed {
+            self.landed += 1;
+        }
+    }
+
+    pub fn record_reward(&mut self, reward: u64) {
+        self.earned += reward;
+    }
+
+    /// Prints the reward for a landed hash with the running totals.
+    pub fn print_reward(&self, reward: u64) {
+        println!(
+            "Reward: {} ORE. Session: {} ORE ({} per round, {} per hour), {} SOL spent, {} ORE/SOL",
+            amount_u64_to_string(reward),
+            amount_u64_to_string(self.earned),
+            amount_u64_to_string(self.earned_per_round()),
+            amount_u64_to_string(self.earned_per_hour()),
+            lamports_to_sol(self.spent()),
+            self.ore_per_sol()
+                .map_or("-".to_string(), |rate| format!("{:.2}", rate))
+        );
+    }
+
+    fn earned_per_round(&self) -> u64 {
+        self.earned.checked_div(self.rounds).unwrap_or(0)
+    }
+
+    fn earned_per_hour(&self) -> u64 {
+        let hours = self.started.elapsed().as_secs_f64() / 3600.0;
+        (self.earned as f64 / hours.max(f64::EPSILON)) as u64
+    }
+
+    /// Priority fees and tips paid this session, in lamports.
+    fn spent(&self) -> u64 {
+        METRICS.spend().saturating_sub(self.spent_at_start)
+    }
+
+    fn ore_per_sol(&self) -> Option<f64> {
+        let spent = lamports_to_sol(self.spent());
+        spent
+            .gt(&0.0)
+            .then(|| amount_u64_to_f64(self.earned) / spent)
+    }
+
+    pub fn print_summary(&self) {
+        let landed_rate = if self.submitted.gt(&0) {
+            self.landed as f64 / self.submitted as f64 * 100.0
+        } else {
+            0.0
+        };
+        let avg_difficulty = if self.rounds.gt(&0) {
+            self.difficulty_total as f64 / self.rounds as f64
+        } else {
+            0.0
+        };
+        let elapsed = self.started.elapsed().as_secs();
+        println!("\n{}", "Session summary".bold());
+        println!(
+            "  Duration: {}h {}m {}s",
+            elapsed / 3600,
+            elapsed % 3600 / 60,
+            elapsed % 60
+        );
+        println!("  Rounds: {}", self.rounds);
+        println!(
+            "  Landed: {}/{} ({:.1}%)",
+            self.landed, self.submitted, landed_rate
+        );
+        println!("  ORE earned: {}", amount_u64_to_string(self.earned));
+        println!(
+            "  ORE per round: {}",
+            amount_u64_to_string(self.earned_per_round())
+        );
+        println!(
+            "  ORE per hour: {}",
+            amount_u64_to_string(self.earned_per_hour())
+        );
+        println!("  SOL spent: {}", lamports_to_sol(self.spent()));
+        if let Some(rate) = self.ore_per_sol() {
+            println!("  ORE per SOL: {:.2}", rate);
+        }
+        println!("  Average difficulty: {:.1}", avg_difficulty);
+    }
+}
@@ -0,0 +1,45 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use colored::*;
+use tokio::signal::unix::{signal, SignalKind};
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Tracks shutdown requests from SIGINT and SIGTERM. The first signal asks the miner
+/// to finish its current round; a second signal exits immediately.
+#[derive(Clone)]
+pub struct Shutdown;
+
+impl Shutdown {
+    pub fn listen() -> Self {
+        tokio::spawn(async move {
+            let mut sigint = signal(SignalKind::interrupt()).expect("Failed to listen for SIGINT");
+            let mut sigterm =
+                signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+            loop {
+                tokio::select! {
+                    _ = sigint.recv() => {}
+                    _ = sigterm.recv() => {}
+                }
+                if REQUESTED.swap(true, Ordering::Relaxed) {
+                    println!("\n{} Exiting immediately", "WARNING".bold().yellow());
+                    std::process::exit(130);
+                }
+                println!(
+                    "\n{} Finishing the current round. Signal again to exit immediately.",
+                    "WARNING".bold().yellow()
+                );
+            }
+        });
+        Self
+    }
+
+    /// Asks the miner to finish its current round, as if a signal had been received.
+    pub fn request() {
+        REQUESTED.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_requested(&self) -> bool {
+        REQUESTED.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,62 @@
+use std::{sync::Arc, time::Duration};
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_rpc_client::rpc_client::RpcClientConfig;
+use solana_sdk::{
+    commitment_config::CommitmentConfig, signature::keypair_from_seed, signer::Signer,
+};
+
+use crate::{
+    args::SimulateArgs,
+    fake_chain::{FakeChain, FakeChainConfig},
+    send_and_confirm::Sender,
+    shutdown::Shutdown,
+    Miner,
+};
+
+impl Miner {
+    /// Mines against a fake chain. The seed fixes challenges, landing outcomes, and the
+    /// miner's random choices, but the chain clock and hashing run in real time, so the
+    /// difficulties found still depend on the machine's speed.
+    pub async fn simulate(&self, args: SimulateArgs) {
+        if !(0.0..=1.0).contains(&args.landing_probability) {
+            eprintln!("error: Landing probability must be between 0 and 1");
+            std::process::exit(1);
+        }
+
+        // Run the real mining loop against an in-process chain with a seeded keypair
+        let chain = FakeChain::new(FakeChainConfig {
+            seed: args.seed,
+            min_difficulty: args.min_difficulty,
+            landing_probability: args.landing_probability,
+            latency: Duration::from_millis(args.latency_ms),
+        });
+        let rpc_client = RpcClient::new_sender(
+            chain,
+            RpcClientConfig::with_commitment(CommitmentConfig::confirmed()),
+        );
+        let mut seed = [0; 32];
+        seed[..8].copy_from_slice(&args.seed.to_le_bytes());
+        let keypair = keypair_from_seed(&seed).expect("Failed to derive keypair");
+        println!("Simulating miner {} (seed {})", keypair.pubkey(), args.seed);
+        let miner = Miner::new(
+            Arc::new(rpc_client),
+            self.priority_fee,
+            Some(keypair.to_base58_string()),
+        );
+
+        // Stop after the requested duration
+        if let Some(duration) = args.duration {
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_secs(duration)).await;
+                Shutdown::request();
+            });
+        }
+
+        // Bundles would go to the real block engine, so always send through the fake RPC
+        let mut mine_args = args.mine;
+        mine_args.sender = Sender::Rpc;
+        mine_args.seed = Some(args.seed);
+        miner.mine(mine_args).await;
+    }
+}
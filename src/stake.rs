@@ -1,7 +1,8 @@
 use std::str::FromStr;
 
+use solana_client::client_error::Result as ClientResult;
 use solana_program::pubkey::Pubkey;
-use solana_sdk::signature::Signer;
+use solana_sdk::signature::{Signature, Signer};
 
 use crate::{
     args::StakeArgs, cu_limits::CU_LIMIT_CLAIM, send_and_confirm::ComputeBudget,
@@ -40,4 +41,26 @@ impl Miner {
             .await
             .ok();
     }
+
+    /// The ORE balance of the signer's token account, if it exists.
+    pub async fn wallet_balance(&self) -> Option<u64> {
+        let tokens = spl_associated_token_account::get_associated_token_address(
+            &self.signer().pubkey(),
+            &ore_api::consts::MINT_ADDRESS,
+        );
+        let token_account = self.rpc_client.get_token_account(&tokens).await.ok()??;
+        u64::from_str(token_account.token_amount.amount.as_str()).ok()
+    }
+
+    /// Stakes from the signer's token account without asking for confirmation.
+    pub async fn stake_from_wallet(&self, amount: u64) -> ClientResult<Signature> {
+        let signer = self.signer();
+        let tokens = spl_associated_token_account::get_associated_token_address(
+            &signer.pubkey(),
+            &ore_api::consts::MINT_ADDRESS,
+        );
+        let ix = ore_api::instruction::stake(signer.pubkey(), tokens, amount);
+        self.send_and_confirm(&[ix], ComputeBudget::Fixed(CU_LIMIT_CLAIM), false)
+            .await
+    }
 }
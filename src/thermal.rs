@@ -0,0 +1,181 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use colored::*;
+
+use crate::throttle::Throttle;
+
+/// How often temperatures are read.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Sheds hashing threads while the hottest thermal zone is above a high watermark, one per
+/// reading, and restores them one per reading once it cools below a low watermark.
+pub struct Thermal {
+    zones: Vec<PathBuf>,
+    high: f64,
+    low: f64,
+}
+
+impl Thermal {
+    /// Finds the `thermal_zone*/temp` files in a directory laid out like
+    /// `/sys/class/thermal`.
+    pub fn new(dir: &Path, high: f64, low: f64) -> Result<Self, String> {
+        if low.ge(&high) {
+            return Err(format!(
+                "Low temperature ({}°C) must be below high temperature ({}°C)",
+                low, high
+            ));
+        }
+        let entries = fs::read_dir(dir)
+            .map_err(|err| format!("Could not read `{}`: {}", dir.display(), err))?;
+        let mut zones: Vec<PathBuf> = entries
+            .flatten()
+            .filter(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with("thermal_zone")
+            })
+            .map(|entry| entry.path().join("temp"))
+            .filter(|path| path.is_file())
+            .collect();
+        zones.sort();
+        if zones.is_empty() {
+            return Err(format!("No thermal zones found in `{}`", dir.display()));
+        }
+        Ok(Self { zones, high, low })
+    }
+
+    /// The temperature of the hottest zone in degrees Celsius, if any could be read.
+    fn temperature(&self) -> Option<f64> {
+        self.zones
+            .iter()
+            .filter_map(|path| fs::read_to_string(path).ok())
+            .filter_map(|s| s.trim().parse::<f64>().ok())
+            .map(|millidegrees| millidegrees / 1000.0)
+            .reduce(f64::max)
+    }
+
+    /// Polls the thermal zones, adjusting the throttle's active threads.
+    pub fn watch(self, throttle: Throttle) {
+        tokio::spawn(async move {
+            loop {
+                self.adjust(&throttle);
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        });
+    }
+
+    /// Takes one reading and sheds or restores a thread if it crosses a watermark.
+    fn adjust(&self, throttle: &Throttle) {
+        let Some(temp) = self.temperature() else {
+            return;
+        };
+        let active = throttle.active_threads();
+        let next = if temp.gt(&self.high) {
+            active.saturating_sub(1).max(1)
+        } else if temp.lt(&self.low) {
+            (active + 1).min(throttle.threads())
+        } else {
+            active
+        };
+        if next.ne(&active) {
+            throttle.set_active_threads(next);
+            let (status, word, watermark) = if next.lt(&active) {
+                ("WARNING".bold().yellow(), "above", self.high)
+            } else {
+                ("OK".bold().green(), "below", self.low)
+            };
+            println!(
+                "{} Temperature {:.1}°C is {} {}°C, hashing on {} of {} threads",
+                status,
+                temp,
+                word,
+                watermark,
+                next,
+                throttle.threads()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory laid out like `/sys/class/thermal`, removed on drop.
+    struct Sysfs(PathBuf);
+
+    impl Sysfs {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("ore-{}-{}", name, std::process::id()));
+            fs::create_dir_all(dir.join("cooling_device0")).unwrap();
+            for zone in ["thermal_zone0", "thermal_zone1"] {
+                fs::create_dir_all(dir.join(zone)).unwrap();
+            }
+            Self(dir)
+        }
+
+        fn set(&self, zone: &str, celsius: f64) {
+            let millidegrees = (celsius * 1000.0) as i64;
+            fs::write(
+                self.0.join(zone).join("temp"),
+                format!("{}\n", millidegrees),
+            )
+            .unwrap();
+        }
+    }
+
+    impl Drop for Sysfs {
+        fn drop(&mut self) {
+            fs::remove_dir_all(&self.0).ok();
+        }
+    }
+
+    #[test]
+    fn finds_zones() {
+        let sysfs = Sysfs::new("thermal-zones");
+        assert!(Thermal::new(&sysfs.0, 80.0, 70.0).is_err());
+        sysfs.set("thermal_zone0", 50.0);
+        sysfs.set("thermal_zone1", 60.0);
+        let thermal = Thermal::new(&sysfs.0, 80.0, 70.0).unwrap();
+        assert_eq!(thermal.zones.len(), 2);
+        assert_eq!(thermal.temperature(), Some(60.0));
+        assert!(Thermal::new(&sysfs.0, 70.0, 70.0).is_err());
+    }
+
+    #[test]
+    fn sheds_and_restores_threads() {
+        let sysfs = Sysfs::new("thermal-adjust");
+        sysfs.set("thermal_zone0", 50.0);
+        sysfs.set("thermal_zone1", 50.0);
+        let thermal = Thermal::new(&sysfs.0, 80.0, 70.0).unwrap();
+        let throttle = Throttle::new(3);
+
+        // The hottest zone sheds one thread per reading, keeping at least one
+        sysfs.set("thermal_zone1", 85.0);
+        let mut active = vec![];
+        for _ in 0..4 {
+            thermal.adjust(&throttle);
+            active.push(throttle.active_threads());
+        }
+        assert_eq!(active, [2, 1, 1, 1]);
+
+        // Between the watermarks nothing changes
+        sysfs.set("thermal_zone1", 75.0);
+        thermal.adjust(&throttle);
+        assert_eq!(throttle.active_threads(), 1);
+
+        // Below the low watermark threads come back one per reading, up to all of them
+        sysfs.set("thermal_zone1", 65.0);
+        active.clear();
+        for _ in 0..3 {
+            thermal.adjust(&throttle);
+            active.push(throttle.active_threads());
+        }
+        assert_eq!(active, [2, 3, 3]);
+    }
+}
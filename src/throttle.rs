@@ -0,0 +1,99 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use colored::*;
+
+/// How often the CPU limit file is checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Limits the CPU use of hashing threads. After each hash, a worker sleeps in proportion
+/// to the time the hash took, so it is busy for the limit's share of the time. Workers
+/// beyond the number of active threads idle instead of hashing. Both limits are shared
+/// with the workers and can change while they hash.
+#[derive(Clone)]
+pub struct Throttle {
+    cpu_limit: Arc<AtomicU64>,
+    threads: u64,
+    active_threads: Arc<AtomicU64>,
+}
+
+impl Throttle {
+    pub fn new(threads: u64) -> Self {
+        Self {
+            cpu_limit: Arc::new(AtomicU64::new(100)),
+            threads,
+            active_threads: Arc::new(AtomicU64::new(threads)),
+        }
+    }
+
+    pub fn threads(&self) -> u64 {
+        self.threads
+    }
+
+    /// The number of workers that should be hashing.
+    pub fn active_threads(&self) -> u64 {
+        self.active_threads.load(Ordering::Relaxed)
+    }
+
+    /// Sets the number of workers that should be hashing, keeping at least one.
+    pub fn set_active_threads(&self, threads: u64) {
+        self.active_threads
+            .store(threads.clamp(1, self.threads.max(1)), Ordering::Relaxed);
+    }
+
+    pub fn is_active(&self, worker: usize) -> bool {
+        (worker as u64).lt(&self.active_threads())
+    }
+
+    /// The target CPU use of each hashing thread, in percent.
+    pub fn cpu_limit(&self) -> u64 {
+        self.cpu_limit.load(Ordering::Relaxed)
+    }
+
+    pub fn set_cpu_limit(&self, percent: u64) {
+        self.cpu_limit.store(percent, Ordering::Relaxed);
+    }
+
+    /// Sleeps after `busy` time spent hashing, if limited. Returns whether it slept.
+    pub fn pace(&self, busy: Duration) -> bool {
+        let limit = self.cpu_limit();
+        if limit.ge(&100) || limit.eq(&0) {
+            return false;
+        }
+        std::thread::sleep(busy.mul_f64((100 - limit) as f64 / limit as f64));
+        true
+    }
+
+    /// Polls a file holding a CPU limit in percent, applying it whenever it changes.
+    pub fn watch_file(&self, path: String) {
+        let throttle = self.clone();
+        tokio::spawn(async move {
+            let mut last = None;
+            loop {
+                let contents = tokio::fs::read_to_string(&path).await;
+                let value = contents.map(|s| s.trim().to_string()).ok();
+                if value.ne(&last) {
+                    match value.as_deref().map(str::parse::<u64>) {
+                        Some(Ok(percent)) if (1..=100).contains(&percent) => {
+                            throttle.set_cpu_limit(percent);
+                            println!("CPU limit set to {}%", percent);
+                        }
+                        _ => println!(
+                            "{} Expected a CPU limit from 1 to 100 in `{}`, keeping {}%",
+                            "WARNING".bold().yellow(),
+                            path,
+                            throttle.cpu_limit()
+                        ),
+                    }
+                    last = value;
+                }
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        });
+    }
+}
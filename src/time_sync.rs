@@ -0,0 +1,113 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+
+use crate::utils::get_clock;
+
+/// The number of recent clock fetches used to estimate the offset.
+const MAX_SAMPLES: usize = 16;
+
+/// Bounds on chain time minus local time from one clock fetch, in seconds.
+#[derive(Clone)]
+struct Sample {
+    min_offset: f64,
+    max_offset: f64,
+    rtt: Duration,
+}
+
+/// Tracks chain time against the local monotonic clock. Each fetch of the clock sysvar
+/// bounds the offset between the two by the RPC round trip and the one-second resolution
+/// of `unix_timestamp`; the bounds from recent fetches are intersected.
+#[derive(Clone)]
+pub struct TimeSync {
+    origin: Instant,
+    samples: VecDeque<Sample>,
+    offset: f64,
+}
+
+impl TimeSync {
+    pub fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+            samples: VecDeque::new(),
+            offset: 0.0,
+        }
+    }
+
+    /// Fetches the chain clock and refines the offset estimate.
+    pub async fn sync(&mut self, client: &RpcClient) {
+        let sent = Instant::now();
+        let clock = get_clock(client).await;
+        let received = Instant::now();
+
+        // The timestamp was read between sending and receiving, and chain time was
+        // within one second after it
+        let timestamp = clock.unix_timestamp as f64;
+        self.samples.push_back(Sample {
+            min_offset: timestamp - self.local(received),
+            max_offset: timestamp + 1.0 - self.local(sent),
+            rtt: received - sent,
+        });
+        if self.samples.len().gt(&MAX_SAMPLES) {
+            self.samples.pop_front();
+        }
+
+        // Start over from the latest fetch if the chain clock has drifted
+        let (lo, hi) = self.bounds();
+        if lo.gt(&hi) {
+            self.samples.drain(..self.samples.len() - 1);
+        }
+        let (lo, hi) = self.bounds();
+        self.offset = (lo + hi) / 2.0;
+    }
+
+    /// The estimated chain time, in seconds since the unix epoch.
+    pub fn now(&self) -> f64 {
+        self.local(Instant::now()) + self.offset
+    }
+
+    pub fn unix_timestamp(&self) -> i64 {
+        self.now().floor() as i64
+    }
+
+    /// The local instant at which the chain clock reaches a timestamp.
+    pub fn instant_at(&self, timestamp: i64) -> Instant {
+        let secs = timestamp as f64 - self.now();
+        Instant::now() + Duration::from_secs_f64(secs.max(0.0))
+    }
+
+    /// How far chain time is ahead of the system clock, in seconds.
+    pub fn skew(&self) -> f64 {
+        let system = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        self.now() - system
+    }
+
+    /// Half the width of the range the offset is known to lie in, in seconds.
+    pub fn uncertainty(&self) -> f64 {
+        let (lo, hi) = self.bounds();
+        (hi - lo).max(0.0) / 2.0
+    }
+
+    /// The round trip time of the latest clock fetch.
+    pub fn rtt(&self) -> Duration {
+        self.samples.back().map(|s| s.rtt).unwrap_or_default()
+    }
+
+    fn bounds(&self) -> (f64, f64) {
+        self.samples
+            .iter()
+            .fold((f64::MIN, f64::MAX), |(lo, hi), s| {
+                (lo.max(s.min_offset), hi.min(s.max_offset))
+            })
+    }
+
+    fn local(&self, instant: Instant) -> f64 {
+        instant.duration_since(self.origin).as_secs_f64()
+    }
+}
@@ -0,0 +1,482 @@
+use std::{fs, path::Path, str::FromStr};
+
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, Utc};
+
+/// Where the system keeps the IANA timezone database.
+const ZONEINFO_DIR: &str = "/usr/share/zoneinfo";
+
+/// The time of day a daylight saving rule switches at, when not given, in seconds.
+const DEFAULT_SWITCH_TIME: i64 = 2 * 3600;
+
+/// A timezone: a fixed UTC offset, or a zone whose offset follows daylight saving time.
+#[derive(Clone, Debug)]
+pub enum Zone {
+    Fixed(FixedOffset),
+    Named(NamedZone),
+}
+
+impl FromStr for Zone {
+    type Err = String;
+
+    /// Parses a UTC offset like `+02:00`, an IANA zone like `Europe/Berlin` from the system
+    /// database, or a POSIX TZ string like `CET-1CEST,M3.5.0,M10.5.0/3`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(offset) = s.parse::<FixedOffset>() {
+            return Ok(Self::Fixed(offset));
+        }
+        let path = Path::new(ZONEINFO_DIR).join(s);
+        if !s.contains("..") {
+            if let Some(zone) = fs::read(path)
+                .ok()
+                .and_then(|data| NamedZone::from_tzif(&data))
+            {
+                return Ok(Self::Named(zone));
+            }
+        }
+        Rule::parse(s)
+            .map(|rule| Self::Named(NamedZone::from_rule(rule)))
+            .ok_or_else(|| {
+                format!(
+                    "Invalid timezone `{}`, expected a UTC offset like +02:00 or a zone like Europe/Berlin",
+                    s
+                )
+            })
+    }
+}
+
+impl Zone {
+    /// The offset from UTC in effect at an instant.
+    pub fn offset_at(&self, time: DateTime<Utc>) -> FixedOffset {
+        match self {
+            Self::Fixed(offset) => *offset,
+            Self::Named(zone) => FixedOffset::east_opt(zone.offset_at(time.timestamp()))
+                .unwrap_or_else(|| FixedOffset::east_opt(0).unwrap()),
+        }
+    }
+}
+
+/// A zone's offsets over time: the transitions it has recorded, then a recurring rule.
+#[derive(Clone, Debug)]
+pub struct NamedZone {
+    /// When each offset took effect, in unix seconds, and the offset in seconds east of UTC.
+    transitions: Vec<(i64, i32)>,
+    /// The offset before the first transition.
+    initial: i32,
+    /// The rule past the last transition, if any.
+    rule: Option<Rule>,
+}
+
+impl NamedZone {
+    fn from_rule(rule: Rule) -> Self {
+        Self {
+            transitions: vec![],
+            initial: rule.std,
+            rule: Some(rule),
+        }
+    }
+
+    /// Parses a compiled TZif file, as laid out in RFC 8536.
+    fn from_tzif(data: &[u8]) -> Option<Self> {
+        let mut reader = Reader { data, pos: 0 };
+        let header = reader.header()?;
+        if header.version.lt(&b'2') {
+            return reader.block(&header, 4).map(|(transitions, initial)| Self {
+                transitions,
+                initial,
+                rule: None,
+            });
+        }
+
+        // Skip the 32-bit block to the 64-bit one, which is followed by the rule
+        reader.take(header.block_len(4))?;
+        let header = reader.header()?;
+        let (transitions, initial) = reader.block(&header, 8)?;
+        let footer = reader.data.get(reader.pos..)?;
+        let footer = std::str::from_utf8(footer).ok()?.trim();
+        Some(Self {
+            transitions,
+            initial,
+            rule: Rule::parse(footer),
+        })
+    }
+
+    fn offset_at(&self, time: i64) -> i32 {
+        let next = self.transitions.partition_point(|(at, _)| at.le(&time));
+        match (next, &self.rule) {
+            (next, Some(rule)) if next.eq(&self.transitions.len()) => rule.offset_at(time),
+            (0, _) => self.initial,
+            (next, _) => self.transitions[next - 1].1,
+        }
+    }
+}
+
+struct Header {
+    version: u8,
+    isutcnt: usize,
+    isstdcnt: usize,
+    leapcnt: usize,
+    timecnt: usize,
+    typecnt: usize,
+    charcnt: usize,
+}
+
+impl Header {
+    /// The length of the data block following the header, for times of `time_len` bytes.
+    fn block_len(&self, time_len: usize) -> usize {
+        self.timecnt * (time_len + 1)
+            + self.typecnt * 6
+            + self.charcnt
+            + self.leapcnt * (time_len + 4)
+            + self.isstdcnt
+            + self.isutcnt
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Option<&[u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn int(&mut self, len: usize) -> Option<i64> {
+        let bytes = self.take(len)?;
+        let value = bytes
+            .iter()
+            .fold(0u64, |value, byte| value << 8 | *byte as u64);
+        // Sign extend from `len` bytes
+        let shift = 64 - 8 * len as u32;
+        Some(((value << shift) as i64) >> shift)
+    }
+
+    fn header(&mut self) -> Option<Header> {
+        if self.take(4)?.ne(b"TZif") {
+            return None;
+        }
+        let version = self.take(16)?[0];
+        let mut count = || self.int(4).map(|n| n as usize);
+        Some(Header {
+            version,
+            isutcnt: count()?,
+            isstdcnt: count()?,
+            leapcnt: count()?,
+            timecnt: count()?,
+            typecnt: count()?,
+            charcnt: count()?,
+        })
+    }
+
+    /// Reads the transitions of a data block and the offset before the first of them.
+    fn block(&mut self, header: &Header, time_len: usize) -> Option<(Vec<(i64, i32)>, i32)> {
+        let start = self.pos;
+        let times = (0..header.timecnt)
+            .map(|_| self.int(time_len))
+            .collect::<Option<Vec<i64>>>()?;
+        let indices = self.take(header.timecnt)?.to_vec();
+        let offsets = (0..header.typecnt)
+            .map(|_| {
+                let offset = self.int(4)? as i32;
+                self.take(2)?;
+                Some(offset)
+            })
+            .collect::<Option<Vec<i32>>>()?;
+        let transitions = times
+            .into_iter()
+            .zip(indices)
+            .map(|(at, index)| Some((at, *offsets.get(index as usize)?)))
+            .collect::<Option<Vec<(i64, i32)>>>()?;
+        self.pos = start + header.block_len(time_len);
+        Some((transitions, *offsets.first()?))
+    }
+}
+
+/// A POSIX TZ rule, like `CET-1CEST,M3.5.0,M10.5.0/3`, giving a standard offset and any
+/// daylight saving offset with the dates it starts and ends each year.
+#[derive(Clone, Debug)]
+struct Rule {
+    /// Seconds east of UTC.
+    std: i32,
+    dst: Option<(i32, Switch, Switch)>,
+}
+
+impl Rule {
+    fn parse(s: &str) -> Option<Self> {
+        let mut parser = Parser { s, pos: 0 };
+        parser.name()?;
+        let std = -parser.offset()?;
+        if parser.is_done() {
+            return Some(Self { std, dst: None });
+        }
+        parser.name()?;
+        let dst = if parser.peek().eq(&Some(',')) {
+            std + 3600
+        } else {
+            -parser.offset()?
+        };
+        parser.expect(',')?;
+        let start = parser.switch()?;
+        parser.expect(',')?;
+        let end = parser.switch()?;
+        parser.is_done().then_some(Self {
+            std,
+            dst: Some((dst, start, end)),
+        })
+    }
+
+    fn offset_at(&self, time: i64) -> i32 {
+        let Some((dst, start, end)) = &self.dst else {
+            return self.std;
+        };
+        let Some(year) = DateTime::from_timestamp(time + self.std as i64, 0).map(|t| t.year())
+        else {
+            return self.std;
+        };
+
+        // Daylight saving starts in standard time and ends in daylight saving time. South
+        // of the equator it ends early in the year and starts late.
+        let (Some(start), Some(end)) = (start.at(year, self.std), end.at(year, *dst)) else {
+            return self.std;
+        };
+        let in_dst = if start.lt(&end) {
+            time.ge(&start) && time.lt(&end)
+        } else {
+            time.lt(&end) || time.ge(&start)
+        };
+        if in_dst {
+            *dst
+        } else {
+            self.std
+        }
+    }
+}
+
+/// A yearly date and local time at which a rule switches offsets.
+#[derive(Clone, Debug)]
+struct Switch {
+    day: Day,
+    /// Seconds past local midnight, which may be negative or past a day.
+    time: i64,
+}
+
+#[derive(Clone, Debug)]
+enum Day {
+    /// Day `d` of the week (0 is Sunday) in week `w` of month `m`, where week 5 is the last.
+    Month(u32, u32, u32),
+    /// Day 1 to 365, never counting February 29.
+    Julian(u32),
+    /// Day 0 to 365, counting February 29.
+    Ordinal(u32),
+}
+
+impl Switch {
+    /// The unix time of the switch in a year, from a local time at `offset`.
+    fn at(&self, year: i32, offset: i32) -> Option<i64> {
+        let date = match self.day {
+            Day::Month(m, w, d) => {
+                let first = NaiveDate::from_ymd_opt(year, m, 1)?;
+                let first_day = first.weekday().num_days_from_sunday();
+                let mut date =
+                    first + Duration::days(((d + 7 - first_day) % 7 + (w - 1) * 7) as i64);
+                while date.month().ne(&m) {
+                    date -= Duration::days(7);
+                }
+                date
+            }
+            Day::Julian(n) => {
+                let leap = NaiveDate::from_ymd_opt(year, 2, 29).is_some() && n.ge(&60);
+                NaiveDate::from_ymd_opt(year, 1, 1)? + Duration::days((n - 1 + leap as u32) as i64)
+            }
+            Day::Ordinal(n) => NaiveDate::from_ymd_opt(year, 1, 1)? + Duration::days(n as i64),
+        };
+        let midnight = date.and_hms_opt(0, 0, 0)?.and_utc().timestamp();
+        Some(midnight + self.time - offset as i64)
+    }
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.s[self.pos..].chars().next()
+    }
+
+    fn is_done(&self) -> bool {
+        self.pos.eq(&self.s.len())
+    }
+
+    fn expect(&mut self, c: char) -> Option<()> {
+        self.peek().eq(&Some(c)).then(|| self.pos += 1)
+    }
+
+    /// Reads characters while `f` holds.
+    fn read(&mut self, f: impl Fn(char) -> bool) -> &str {
+        let start = self.pos;
+        while self.peek().is_some_and(&f) {
+            self.pos += 1;
+        }
+        &self.s[start..self.pos]
+    }
+
+    fn number(&mut self) -> Option<u32> {
+        self.read(|c| c.is_ascii_digit()).parse().ok()
+    }
+
+    /// A zone abbreviation, like `CET` or `<+0330>`.
+    fn name(&mut self) -> Option<()> {
+        if self.expect('<').is_some() {
+            self.read(|c| c.ne(&'>'));
+            return self.expect('>');
+        }
+        self.read(|c| c.is_ascii_alphabetic())
+            .len()
+            .ge(&3)
+            .then_some(())
+    }
+
+    /// A signed `hh[:mm[:ss]]`, in seconds.
+    fn offset(&mut self) -> Option<i32> {
+        let sign = match self.peek() {
+            Some('-') => -1,
+            Some('+') => 1,
+            _ => return self.hms().map(|secs| secs as i32),
+        };
+        self.pos += 1;
+        self.hms().map(|secs| sign * secs as i32)
+    }
+
+    fn hms(&mut self) -> Option<u32> {
+        let mut secs = self.number()? * 3600;
+        for unit in [60, 1] {
+            if self.expect(':').is_none() {
+                break;
+            }
+            secs += self.number()? * unit;
+        }
+        Some(secs)
+    }
+
+    /// A date like `M3.5.0`, `J60` or `59`, with an optional `/time`.
+    fn switch(&mut self) -> Option<Switch> {
+        let day = match self.peek()? {
+            'M' => {
+                self.pos += 1;
+                let m = self.number()?;
+                self.expect('.')?;
+                let w = self.number()?;
+                self.expect('.')?;
+                let d = self.number()?;
+                ((1..=12).contains(&m) && (1..=5).contains(&w) && d.lt(&7))
+                    .then_some(Day::Month(m, w, d))?
+            }
+            'J' => {
+                self.pos += 1;
+                let n = self.number()?;
+                (1..=365).contains(&n).then_some(Day::Julian(n))?
+            }
+            _ => {
+                let n = self.number()?;
+                n.le(&365).then_some(Day::Ordinal(n))?
+            }
+        };
+        let time = match self.expect('/') {
+            Some(()) => self.offset()? as i64,
+            None => DEFAULT_SWITCH_TIME,
+        };
+        Some(Switch { day, time })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn offset(zone: &Zone, y: i32, mo: u32, d: u32, h: u32, mi: u32, s: u32) -> i32 {
+        let time = Utc.with_ymd_and_hms(y, mo, d, h, mi, s).unwrap();
+        zone.offset_at(time).local_minus_utc()
+    }
+
+    #[test]
+    fn parses_fixed_offsets() {
+        let zone: Zone = "+02:00".parse().unwrap();
+        assert_eq!(offset(&zone, 2024, 7, 1, 0, 0, 0), 7200);
+        let zone: Zone = "<+0330>-3:30".parse().unwrap();
+        assert_eq!(offset(&zone, 2024, 7, 1, 0, 0, 0), 12600);
+        assert!("Nowhere/Invalid".parse::<Zone>().is_err());
+        assert!("../../etc/passwd".parse::<Zone>().is_err());
+    }
+
+    #[test]
+    fn follows_daylight_saving() {
+        // Central Europe switches at 01:00 UTC on the last Sundays of March and October
+        let zone: Zone = "CET-1CEST,M3.5.0,M10.5.0/3".parse().unwrap();
+        assert_eq!(offset(&zone, 2024, 3, 31, 0, 59, 59), 3600);
+        assert_eq!(offset(&zone, 2024, 3, 31, 1, 0, 0), 7200);
+        assert_eq!(offset(&zone, 2024, 10, 27, 0, 59, 59), 7200);
+        assert_eq!(offset(&zone, 2024, 10, 27, 1, 0, 0), 3600);
+
+        // The US switches at 02:00 local on the second Sunday of March
+        let zone: Zone = "EST5EDT,M3.2.0,M11.1.0".parse().unwrap();
+        assert_eq!(offset(&zone, 2024, 3, 10, 6, 59, 59), -18000);
+        assert_eq!(offset(&zone, 2024, 3, 10, 7, 0, 0), -14400);
+        assert_eq!(offset(&zone, 2024, 11, 3, 5, 59, 59), -14400);
+        assert_eq!(offset(&zone, 2024, 11, 3, 6, 0, 0), -18000);
+
+        // South of the equator daylight saving spans the new year
+        let zone: Zone = "AEST-10AEDT,M10.1.0,M4.1.0/3".parse().unwrap();
+        assert_eq!(offset(&zone, 2024, 1, 15, 0, 0, 0), 39600);
+        assert_eq!(offset(&zone, 2024, 6, 15, 0, 0, 0), 36000);
+        assert_eq!(offset(&zone, 2024, 12, 15, 0, 0, 0), 39600);
+    }
+
+    /// Builds a version 2 TZif file with one transition from +01:00 to +02:00.
+    fn tzif(transition: i64, footer: &str) -> Vec<u8> {
+        let header = |data: &mut Vec<u8>, timecnt: u32, typecnt: u32| {
+            data.extend_from_slice(b"TZif2");
+            data.extend_from_slice(&[0; 15]);
+            for count in [0, 0, 0, timecnt, typecnt, 4] {
+                data.extend_from_slice(&count.to_be_bytes());
+            }
+        };
+        let mut data = vec![];
+        header(&mut data, 0, 1);
+        data.extend_from_slice(&3600i32.to_be_bytes());
+        data.extend_from_slice(&[0, 0]);
+        data.extend_from_slice(b"ABC\0");
+        header(&mut data, 1, 2);
+        data.extend_from_slice(&transition.to_be_bytes());
+        data.push(1);
+        for offset in [3600i32, 7200] {
+            data.extend_from_slice(&offset.to_be_bytes());
+            data.extend_from_slice(&[0, 0]);
+        }
+        data.extend_from_slice(b"ABC\0");
+        data.extend_from_slice(format!("\n{}\n", footer).as_bytes());
+        data
+    }
+
+    #[test]
+    fn reads_tzif() {
+        let transition = Utc
+            .with_ymd_and_hms(2000, 1, 1, 0, 0, 0)
+            .unwrap()
+            .timestamp();
+        let zone = Zone::Named(NamedZone::from_tzif(&tzif(transition, "<+02>-2")).unwrap());
+        assert_eq!(offset(&zone, 1999, 12, 31, 23, 59, 59), 3600);
+        assert_eq!(offset(&zone, 2000, 1, 1, 0, 0, 0), 7200);
+
+        // The rule takes over past the recorded transitions
+        let zone = Zone::Named(NamedZone::from_tzif(&tzif(0, "<+03>-3")).unwrap());
+        assert_eq!(offset(&zone, 2024, 1, 1, 0, 0, 0), 10800);
+        assert!(NamedZone::from_tzif(b"TZif2").is_none());
+    }
+}
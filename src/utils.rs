@@ -3,9 +3,10 @@ use std::io::Read;
 use cached::proc_macro::cached;
 use ore_api::{
     consts::{
-        CONFIG_ADDRESS, MINT_ADDRESS, PROOF, TOKEN_DECIMALS, TOKEN_DECIMALS_V1, TREASURY_ADDRESS,
+        BUS_ADDRESSES, CONFIG_ADDRESS, MINT_ADDRESS, ONE_MINUTE, PROOF, TOKEN_DECIMALS,
+        TOKEN_DECIMALS_V1, TOLERANCE, TREASURY_ADDRESS,
     },
-    state::{Config, Proof, Treasury},
+    state::{Bus, Config, Proof, Treasury},
 };
 use ore_utils::AccountDeserialize;
 use solana_client::nonblocking::rpc_client::RpcClient;
@@ -29,6 +30,17 @@ pub async fn get_config(client: &RpcClient) -> Config {
     *Config::try_from_bytes(&data).expect("Failed to parse config account")
 }
 
+pub async fn get_busses(client: &RpcClient) -> Vec<Bus> {
+    client
+        .get_multiple_accounts(&BUS_ADDRESSES)
+        .await
+        .expect("Failed to get bus accounts")
+        .into_iter()
+        .flatten()
+        .filter_map(|account| Bus::try_from_bytes(&account.data).ok().copied())
+        .collect()
+}
+
 pub async fn get_proof_with_authority(client: &RpcClient, authority: Pubkey) -> Proof {
     let proof_address = proof_pubkey(authority);
     get_proof(client, proof_address).await
@@ -50,6 +62,51 @@ pub async fn get_clock(client: &RpcClient) -> Clock {
     bincode::deserialize::<Clock>(&data).expect("Failed to deserialize clock")
 }
 
+/// Estimates the reward the program would pay out for a hash of the given difficulty,
+/// including the staking multiplier and the penalty for submitting late. The payout is
+/// capped by the bus balance on-chain.
+pub fn calculate_reward(config: &Config, proof: &Proof, difficulty: u32, now: i64) -> u64 {
+    let min_difficulty = config.min_difficulty as u32;
+    if difficulty.lt(&min_difficulty) {
+        return 0;
+    }
+    let mut reward = config
+        .base_reward_rate
+        .saturating_mul(2u64.saturating_pow(difficulty - min_difficulty));
+    if proof.balance.gt(&0)
+        && config.top_balance.gt(&0)
+        && proof.last_stake_at.saturating_add(ONE_MINUTE).lt(&now)
+    {
+        let staking_reward = (reward as u128)
+            .saturating_mul(proof.balance.min(config.top_balance) as u128)
+            .saturating_div(config.top_balance as u128) as u64;
+        reward = reward.saturating_add(staking_reward);
+    }
+
+    // Apply liveness penalty
+    let t_target = proof.last_hash_at.saturating_add(ONE_MINUTE);
+    if now.gt(&t_target.saturating_add(TOLERANCE)) {
+        let secs_late = now.saturating_sub(t_target) as u64;
+        let mins_late = secs_late.saturating_div(ONE_MINUTE as u64);
+        reward = reward.saturating_div(2u64.saturating_pow(mins_late as u32));
+        let remainder_secs = secs_late.saturating_sub(mins_late.saturating_mul(ONE_MINUTE as u64));
+        let penalty = reward
+            .saturating_div(2)
+            .saturating_mul(remainder_secs)
+            .saturating_div(ONE_MINUTE as u64);
+        reward = reward.saturating_sub(penalty);
+    }
+    reward
+}
+
+/// The factor a stake balance multiplies rewards by, once it has been staked a minute.
+pub fn stake_multiplier(config: &Config, balance: u64) -> f64 {
+    if config.top_balance.eq(&0) {
+        return 1.0;
+    }
+    1.0 + balance.min(config.top_balance) as f64 / config.top_balance as f64
+}
+
 pub fn amount_u64_to_string(amount: u64) -> String {
     amount_u64_to_f64(amount).to_string()
 }